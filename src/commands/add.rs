@@ -1,3 +1,4 @@
+use crate::project::Settings;
 use crate::{SmoothlyError, Command, Repo};
 
 pub struct Add {}
@@ -15,7 +16,7 @@ impl Command for Add {
             )
     }
 
-    fn run(&self, args: &clap::ArgMatches, repo: String) -> Result<(), SmoothlyError> {
+    fn run(&self, args: &clap::ArgMatches, repo: String, _: &Settings) -> Result<(), SmoothlyError> {
         let repo = Repo::new(repo)?;
         println!("Name: {}", repo.repoName);
         println!("Add");
@@ -0,0 +1,126 @@
+use std::io::Write;
+
+use crate::project::Settings;
+use crate::srf::{self, Published};
+use crate::{Command, SmoothlyError, State};
+
+pub struct Changelog {}
+
+impl Command for Changelog {
+    fn register(&self) -> clap::App {
+        clap::SubCommand::with_name("changelog")
+            .about("Describe the changes between two published states as Markdown")
+            .args(&super::dir_args())
+            .arg(clap::Arg::with_name("from")
+                .help("Snapshot id or directory of the previous state")
+            ).arg(clap::Arg::with_name("to")
+                .help("Snapshot id or directory of the new state, defaults to the output directory")
+            ).arg(clap::Arg::with_name("file")
+                .help("Repository file to compare")
+                .long("file")
+                .takes_value(true)
+                .default_value("repo.json")
+            ).arg(clap::Arg::with_name("output")
+                .help("Write the changelog to a file instead of the terminal")
+                .long("output")
+                .short("o")
+                .takes_value(true)
+            )
+    }
+
+    fn needs_repo(&self) -> bool {
+        false
+    }
+
+    fn run(&self, args: &clap::ArgMatches, _: String, settings: &Settings) -> Result<(), SmoothlyError> {
+        let (dir, positionals) = super::positionals(args, settings, &["from", "to"], 1, false)?;
+        let file = args.value_of("file").unwrap();
+        let old = Published::locate(&dir, &positionals[0], file)?;
+        let new = match positionals.get(1) {
+            Some(to) => Published::locate(&dir, to, file)?,
+            None => Published::load(&dir, file)?,
+        };
+        let markdown = changelog(&old, &new);
+        match args.value_of("output") {
+            Some(path) => std::fs::File::create(path)?.write_all(markdown.as_bytes())?,
+            None => print!("{}", markdown),
+        }
+        Ok(())
+    }
+}
+
+pub fn changelog(old: &Published, new: &Published) -> String {
+    let mut out = format!("# {}\n\n", new.repo.repoName);
+
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    let mut moved = Vec::new();
+    for arma_mod in new.repo.mods() {
+        let name = &arma_mod.modName;
+        if !old.repo.has_mod(name) {
+            added.push(format!("- {} ({})", name, list(&new.repo.mod_state(name))));
+        } else if list(&old.repo.mod_state(name)) != list(&new.repo.mod_state(name)) {
+            moved.push(format!("- {}: {} -> {}", name, list(&old.repo.mod_state(name)), list(&new.repo.mod_state(name))));
+        }
+    }
+    for arma_mod in old.repo.mods() {
+        if !new.repo.has_mod(&arma_mod.modName) {
+            removed.push(format!("- {}", arma_mod.modName));
+        }
+    }
+    for (title, lines) in &[("Added", &added), ("Removed", &removed), ("Moved", &moved)] {
+        if lines.is_empty() { continue; }
+        out.push_str(&format!("## {}\n\n{}\n\n", title, lines.join("\n")));
+    }
+
+    let mut updated = String::new();
+    for arma_mod in new.repo.mods() {
+        let name = &arma_mod.modName;
+        let (before, after) = match (old.addons.get(name), new.addons.get(name)) {
+            (Some(b), Some(a)) => (b, a),
+            _ => continue,
+        };
+        if before.hash == after.hash { continue; }
+        let mut lines = Vec::new();
+        for file in &after.files {
+            match before.files.iter().find(|f| f.name == file.name) {
+                None => lines.push(format!("- `{}` added ({})", file.name, srf::human(file.size() as u64))),
+                Some(previous) if previous.hash != file.hash => {
+                    let changed = srf::changed_bytes(Some(previous), file);
+                    let delta = file.size() as i64 - previous.size() as i64;
+                    let mut line = format!("- `{}` changed, {} to download ({})", file.name, srf::human(changed), srf::human_delta(delta));
+                    if file.name.ends_with(".pbo") {
+                        let parts = file.parts.iter()
+                            .filter(|p| !previous.parts.iter().any(|o| o.name == p.name && o.hash == p.hash))
+                            .count();
+                        line.push_str(&format!(", {} of {} entries", parts, file.parts.len()));
+                    }
+                    lines.push(line);
+                },
+                _ => {},
+            }
+        }
+        for file in &before.files {
+            if !after.files.iter().any(|f| f.name == file.name) {
+                lines.push(format!("- `{}` removed ({})", file.name, srf::human(file.size() as u64)));
+            }
+        }
+        let delta = srf::addon_size(after) as i64 - srf::addon_size(before) as i64;
+        updated.push_str(&format!("### {} ({})\n\n{}\n\n", name, srf::human_delta(delta), lines.join("\n")));
+    }
+    if !updated.is_empty() {
+        out.push_str(&format!("## Updated\n\n{}", updated));
+    }
+
+    if added.is_empty() && removed.is_empty() && moved.is_empty() && updated.is_empty() {
+        out.push_str("No changes\n");
+    }
+    out
+}
+
+fn list(state: &State) -> &'static str {
+    match state {
+        State::Enabled | State::Disabled => "required",
+        State::OptionalEnabled | State::OptionalDisabled => "optional",
+    }
+}
@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use colored::*;
+
+use crate::project::Settings;
+use crate::conflicts;
+use crate::{Command, Repo, SmoothlyError};
+
+pub struct Conflicts {}
+
+impl Command for Conflicts {
+    fn register(&self) -> clap::App {
+        clap::SubCommand::with_name("conflicts")
+            .about("Find PBOs that override each other across mods")
+    }
+
+    fn run(&self, _: &clap::ArgMatches, repo: String, _: &Settings) -> Result<(), SmoothlyError> {
+        let repo = Repo::new(repo)?;
+        let mut pbos = Vec::new();
+        for name in conflicts::candidates(&repo) {
+            let moddir = PathBuf::from(&repo.basePath).join(&name);
+            if !moddir.is_dir() { continue; }
+            pbos.append(&mut conflicts::scan(&moddir, &name)?);
+        }
+        let found = conflicts::find(&pbos);
+        for conflict in &found {
+            print!("{}", conflict.to_string().yellow());
+        }
+        println!("{} conflicts in {} PBOs", found.len(), pbos.len());
+        Ok(())
+    }
+}
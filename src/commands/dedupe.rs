@@ -0,0 +1,50 @@
+use crate::project::Settings;
+use crate::srf::{self, Published};
+use crate::{dedupe, Command, SmoothlyError};
+
+pub struct Dedupe {}
+
+impl Command for Dedupe {
+    fn register(&self) -> clap::App {
+        clap::SubCommand::with_name("dedupe-report")
+            .about("Find identical files bundled by more than one mod")
+            .args(&super::dir_args())
+            .arg(clap::Arg::with_name("file")
+                .help("Repository file to check")
+                .long("file")
+                .takes_value(true)
+                .default_value("repo.json")
+            ).arg(clap::Arg::with_name("min-size")
+                .help("Ignore files smaller than this many bytes")
+                .long("min-size")
+                .takes_value(true)
+                .default_value("0")
+            )
+    }
+
+    fn needs_repo(&self) -> bool {
+        false
+    }
+
+    fn run(&self, args: &clap::ArgMatches, _: String, settings: &Settings) -> Result<(), SmoothlyError> {
+        let (dir, _) = super::positionals(args, settings, &[], 0, false)?;
+        let min_size: u64 = args.value_of("min-size").unwrap().parse()
+            .map_err(|_| SmoothlyError::GENERIC("--min-size must be a number".to_owned()))?;
+        let published = Published::load(&dir, args.value_of("file").unwrap())?;
+
+        let groups = dedupe::find(&published, min_size);
+        for group in &groups {
+            println!("{} x {} in {} mods ({} wasted)", group.copies.len(), srf::human(group.size), group.mods().len(), srf::human(group.wasted()));
+            for copy in &group.copies {
+                println!(" - {}\\{}", copy.addon, copy.file);
+            }
+        }
+
+        let disk: u64 = groups.iter().map(|g| g.wasted()).sum();
+        let download: u64 = groups.iter().map(|g| g.wasted_download(&published)).sum();
+        println!("{} duplicated files across {} mods", groups.len(), published.addons.len());
+        println!("Wasted disk space: {} of {}", srf::human(disk), srf::human(published.size()));
+        println!("Wasted download with the required mods: {}", srf::human(download));
+        Ok(())
+    }
+}
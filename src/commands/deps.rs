@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use colored::*;
+
+use crate::project::Settings;
+use crate::{conflicts, deps};
+use crate::{Command, Repo, SmoothlyError};
+
+pub struct Deps {}
+
+impl Command for Deps {
+    fn register(&self) -> clap::App {
+        clap::SubCommand::with_name("deps")
+            .about("Check the CfgPatches dependencies between mods and suggest a load order")
+            .arg(clap::Arg::with_name("ignore")
+                .help("Prefixes of patches provided outside of the repository, such as CDLCs")
+                .long("ignore")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+    }
+
+    fn run(&self, args: &clap::ArgMatches, repo: String, _: &Settings) -> Result<(), SmoothlyError> {
+        let repo = Repo::new(repo)?;
+        let ignore: Vec<String> = args.values_of("ignore").map(|v| v.map(|s| s.to_owned()).collect()).unwrap_or_default();
+        let mut patches = Vec::new();
+        for m in repo.mods() {
+            let moddir = PathBuf::from(&repo.basePath).join(&m.modName);
+            if !moddir.is_dir() { continue; }
+            patches.append(&mut deps::scan(&moddir, &m.modName)?);
+        }
+
+        let found = deps::unresolved(&repo, &patches, &ignore);
+        for unresolved in &found {
+            print!("{}", unresolved.to_string().yellow());
+        }
+        println!("{} unresolved dependencies in {} patches", found.len(), patches.len());
+
+        let (order, cycles) = deps::load_order(&conflicts::candidates(&repo), &patches);
+        println!("Suggested load order");
+        for (i, name) in order.iter().enumerate() {
+            if cycles.contains(name) {
+                println!(" {}. {} {}", i + 1, name, "(circular dependency)".yellow());
+            } else {
+                println!(" {}. {}", i + 1, name);
+            }
+        }
+        Ok(())
+    }
+}
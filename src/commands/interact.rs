@@ -1,51 +1,202 @@
-use std::fs::File;
 use std::io::Write;
 
-use crossterm::{InputEvent, KeyEvent, RawScreen, input, cursor};
+use crossterm::{AlternateScreen, ClearType, InputEvent, KeyEvent, input, cursor, terminal};
 use colored::*;
 
-use crate::{SmoothlyError, Command, Repo, Mod, Transaction, State};
+use crate::project::Settings;
+use crate::{SmoothlyError, Command, Repo, Mod, Server, Transaction, State};
 
 pub struct Interact {}
 
+// Lines used by everything but the list: title, tabs, legend, blank, apply, cancel
+const RESERVED_LINES: usize = 6;
+// Width of the longest state label, "Optional (Disabled)"
+const STATE_WIDTH: usize = 19;
+// Width of the longest field label, "Client Parameters"
+const LABEL_WIDTH: usize = 17;
+
 impl Interact {
-    fn tick(mods: &[(String, Transaction, State)], index: usize, page: usize) -> Result<(), SmoothlyError> {
-        let mut cursor = cursor();
-        let c = cursor2index(index, mods.len());
-        let start = page * 8;
-        let mut end = start+8;
-        if end > mods.len() {
-            end = mods.len();
-        }
-        cursor.move_up(12);
-        println!("\rPage: {} ({} - {})                             ", page, start + 1, end);
-        for (i, arma_mod) in mods[start..end].iter().enumerate() {
-            let (name, trans, state) = arma_mod;
-            if let Cursor::Item(_) = c {
-                if i == index - (10 * (index / 10)) {
-                    println!("\r{} {:20} {}                                                                     ", "*".cyan(), color!(name, trans), state!(state));
-                    continue;
-                }
-            }
-            println!("\r  {:20} {}                                                                     ", color!(name, trans), state!(state));
+    fn tick(repo: &Repo, tab: Tab, legend: String, rows: &[(Cursor, String)], selected: &Cursor, size: (usize, usize)) -> Result<(), SmoothlyError> {
+        let (width, height) = size;
+        let cursor = cursor();
+
+        let mut row = 0;
+        let mut line = |text: String| {
+            cursor.goto(0, row as u16).unwrap();
+            print!("{}", text);
+            row += 1;
+        };
+
+        line(pad(&format!("Name: {}", repo.repoName), width));
+        let mut tabs = String::new();
+        let mut tabs_width = 0;
+        for t in &[Tab::Mods, Tab::Settings, Tab::Servers] {
+            let label = format!(" {} ", t.name());
+            tabs_width += label.len();
+            tabs.push_str(&if *t == tab { label.reversed().to_string() } else { label });
         }
-        for _ in 0..(8 - (end - start)) {
-            println!("\r                                                                     ");
+        line(format!("{}{}", tabs, pad(" (Tab)", width.saturating_sub(tabs_width))));
+        line(legend);
+        for (c, text) in rows {
+            let marker = if selected == c { "*".cyan() } else { " ".normal() };
+            line(format!("{} {}", marker, text));
         }
-        println!();
-        if c == Cursor::Apply {
-            println!("\r{} Apply (End)                                                                     ", "*".cyan());
-        } else {
-            println!("\r  Apply (End)                                                                     ");
+        for _ in rows.len()..page_size(height) {
+            line(pad("", width));
         }
-        if c == Cursor::Cancel {
-            println!("\r{} Cancel (q)                                                                     ", "*".cyan());
-        } else {
-            println!("\r  Cancel (q)                                                                     ");
+        line(pad("", width));
+        let marker = if *selected == Cursor::Apply { "*".cyan() } else { " ".normal() };
+        line(format!("{} {}", marker, pad("Apply (End)", width.saturating_sub(2))));
+        let marker = if *selected == Cursor::Cancel { "*".cyan() } else { " ".normal() };
+        line(format!("{} {}", marker, pad("Cancel (q)", width.saturating_sub(2))));
+
+        cursor.hide().unwrap();
+        std::io::stdout().flush()?;
+        Ok(())
+    }
+
+    fn mod_rows(mods: &[(String, Transaction, State)], view: &[usize], start: usize, marked: &[bool], width: usize) -> Vec<(Cursor, String)> {
+        let name_width = name_width(mods, width.saturating_sub(2));
+        view.iter().enumerate().map(|(i, m)| {
+            let (name, trans, state) = &mods[*m];
+            let name = fit(name, name_width);
+            let mark = if marked[*m] { "+".cyan() } else { " ".normal() };
+            (Cursor::Item(start + i), format!("{} {} {}", mark, color!(name, trans), pad(state!(state), width.saturating_sub(name_width + 5))))
+        }).collect()
+    }
+
+    fn field_rows(repo: &Repo, fields: &[Field], editing: &Option<(Field, String)>, width: usize) -> Vec<(Cursor, String)> {
+        fields.iter().enumerate().map(|(i, field)| {
+            let value = match editing {
+                Some((f, buffer)) if f == field => format!("{}_", buffer),
+                _ => field.display(repo),
+            };
+            (Cursor::Item(i), format!("{} {}", pad(field.label(), LABEL_WIDTH), fit(&value, width.saturating_sub(LABEL_WIDTH + 3))))
+        }).collect()
+    }
+
+    fn server_rows(repo: &Repo, width: usize) -> Vec<(Cursor, String)> {
+        repo.servers.iter().enumerate().map(|(i, server)| {
+            let text = format!("{} {}:{}{}", server.name, server.address, server.port, if server.battleEye { " (BattlEye)" } else { "" });
+            (Cursor::Item(i), fit(&text, width.saturating_sub(2)))
+        }).collect()
+    }
+
+    fn review(changes: &[(String, Transaction, String)], size: (usize, usize)) -> Result<(), SmoothlyError> {
+        let (width, height) = size;
+        let cursor = cursor();
+        let name_width = std::cmp::min(
+            changes.iter().map(|c| c.0.chars().count()).max().unwrap_or(0),
+            width.saturating_sub(3),
+        );
+        // Title, blank, blank and the prompt
+        let room = std::cmp::max(1, height.saturating_sub(4));
+
+        let mut row = 0;
+        let mut line = |text: String| {
+            cursor.goto(0, row as u16).unwrap();
+            print!("{}", text);
+            row += 1;
+        };
+
+        line(pad("Pending changes", width));
+        line(pad("", width));
+        if changes.is_empty() {
+            line(pad("No changes", width));
+        }
+        for (i, (name, trans, desc)) in changes.iter().enumerate() {
+            if i + 1 == room && changes.len() > room {
+                line(pad(&format!("... and {} more", changes.len() - i), width));
+                break;
+            }
+            let name = fit(name, name_width);
+            line(format!("{} {}", color!(name, trans), fit(desc, width.saturating_sub(name_width + 1))));
         }
+        line(pad("", width));
+        line(pad("Apply these changes? (y/n)", width));
+
         cursor.hide().unwrap();
+        std::io::stdout().flush()?;
         Ok(())
     }
+
+    /// Build the repository that applying `mods` would produce
+    fn build(repo: &Repo, mods: &[(String, Transaction, State)]) -> Repo {
+        let mut out = repo.clone();
+        out.requiredMods = Vec::new();
+        out.optionalMods = Vec::new();
+        for arma_mod in mods {
+            if arma_mod.1 == Transaction::Add || arma_mod.1 == Transaction::Existing || arma_mod.1 == Transaction::Missing {
+                let new_mod = Mod {
+                    modName: arma_mod.0.clone(),
+                    checkSum: "".to_owned(),
+                    Enabled: arma_mod.2 == State::Enabled || arma_mod.2 == State::OptionalEnabled,
+                };
+                match arma_mod.2 {
+                    State::Enabled | State::Disabled => {
+                        out.requiredMods.push(new_mod);
+                    },
+                    State::OptionalDisabled | State::OptionalEnabled => {
+                        out.optionalMods.push(new_mod);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Differences between the repository on disk and the one about to be written
+    fn changes(old: &Repo, new: &Repo) -> Vec<(String, Transaction, String)> {
+        let mut changes = Vec::new();
+        if old.repoName != new.repoName {
+            changes.push(("Name".to_owned(), Transaction::Update, format!("{} -> {}", old.repoName, new.repoName)));
+        }
+        if old.clientParameters != new.clientParameters {
+            changes.push(("Client Parameters".to_owned(), Transaction::Update, format!("{} -> {}", old.clientParameters, new.clientParameters)));
+        }
+        for server in &new.servers {
+            match old.servers.iter().find(|s| s.name == server.name) {
+                None => changes.push((server.name.clone(), Transaction::Add, "Server added".to_owned())),
+                Some(s) if s != server => changes.push((server.name.clone(), Transaction::Update, "Server changed".to_owned())),
+                _ => {},
+            }
+        }
+        for server in &old.servers {
+            if !new.servers.iter().any(|s| s.name == server.name) {
+                changes.push((server.name.clone(), Transaction::Remove, "Server removed".to_owned()));
+            }
+        }
+        for arma_mod in new.mods() {
+            let name = &arma_mod.modName;
+            if !old.has_mod(name) {
+                changes.push((name.clone(), Transaction::Add, format!("Added as {}", describe(&new.mod_state(name)))));
+            } else if old.mod_state(name) != new.mod_state(name) {
+                changes.push((name.clone(), Transaction::Update, format!("{} -> {}", describe(&old.mod_state(name)), describe(&new.mod_state(name)))));
+            }
+        }
+        for arma_mod in old.mods() {
+            if !new.has_mod(&arma_mod.modName) {
+                changes.push((arma_mod.modName.clone(), Transaction::Remove, "Removed".to_owned()));
+            }
+        }
+        // Mods that stay in the same list but end up in a different position
+        for (before, after) in &[(&old.requiredMods, &new.requiredMods), (&old.optionalMods, &new.optionalMods)] {
+            let kept = |from: &Vec<Mod>, other: &Vec<Mod>| -> Vec<String> {
+                from.iter()
+                    .filter(|m| other.iter().any(|o| o.modName == m.modName))
+                    .map(|m| m.modName.clone())
+                    .collect()
+            };
+            let before_order = kept(before, after);
+            let after_order = kept(after, before);
+            for (i, name) in after_order.iter().enumerate() {
+                if before_order[i] != *name {
+                    changes.push((name.clone(), Transaction::Existing, format!("Moved to position {}", i + 1)));
+                }
+            }
+        }
+        changes
+    }
 }
 
 impl Command for Interact {
@@ -54,10 +205,8 @@ impl Command for Interact {
             .about("Interactively manage the mods in the repository")
     }
 
-    fn run(&self, _: &clap::ArgMatches, repo_path: String) -> Result<(), SmoothlyError> {
-        let mut repo = Repo::new(repo_path.clone())?;
-        println!("Name: {}", repo.repoName);
-        println!("{} - {} - {} - {}", "Existing".purple(), "New".green(), "Remove".red(), "Ignored".white());
+    fn run(&self, _: &clap::ArgMatches, repo_path: String, _: &Settings) -> Result<(), SmoothlyError> {
+        let repo = Repo::new(repo_path.clone())?;
 
         let mut mods = Vec::new();
 
@@ -73,114 +222,310 @@ impl Command for Interact {
             ));
         }
 
-        let screen = RawScreen::into_raw_mode()?;
+        // Mods in repo.json without a folder are kept unless explicitly removed
+        let missing = repo.missing_mods();
+        for name in &missing {
+            mods.push((name.clone(), Transaction::Missing, repo.mod_state(name)));
+        }
+
+        let screen = AlternateScreen::to_alternate(true)?;
+        let terminal = terminal();
 
         let input = input();
         let mut stdin = input.read_async();
 
-        let mut index = 0;
+        // Settings and servers are edited on a copy, mods are merged into it on Apply
+        let mut draft = repo.clone();
+        let mut tab = Tab::Mods;
+        let mut selected = if mods.is_empty() { Cursor::Apply } else { Cursor::Item(0) };
         let mut page = 0;
+        let mut size = (0, 0);
+        let mut review: Option<(Repo, Vec<(String, Transaction, String)>)> = None;
+        let mut server: Option<usize> = None;
+        let mut editing: Option<(Field, String)> = None;
 
-        for _ in 0..13 {
-            println!();
-        }
+        let mut marked = vec![false; mods.len()];
+        let mut anchor = 0;
+        let mut filter = String::new();
+        let mut filtering = false;
+        let mut undo: Vec<Vec<(String, Transaction, State)>> = Vec::new();
+        let mut redo: Vec<Vec<(String, Transaction, State)>> = Vec::new();
 
         loop {
-            Interact::tick(&mods, index, page)?;
+            let view: Vec<usize> = (0..mods.len())
+                .filter(|i| mods[*i].0.to_lowercase().contains(&filter.to_lowercase()))
+                .collect();
+
+            // Redraw everything when the terminal changes size
+            let (width, height) = terminal.terminal_size();
+            let current = ((width as usize).saturating_sub(1), height as usize);
+            if current != size {
+                size = current;
+                terminal.clear(ClearType::All).unwrap();
+                page = match (tab, selected) {
+                    (Tab::Mods, Cursor::Item(i)) => i / page_size(size.1),
+                    _ => std::cmp::min(page, page_count(view.len(), page_size(size.1)) - 1),
+                };
+            }
+            if let Some((ref new_repo, ref changes)) = review {
+                Interact::review(changes, size)?;
+                if let Some(InputEvent::Keyboard(e)) = stdin.next() {
+                    match e {
+                        KeyEvent::Char('y') | KeyEvent::Char('Y') => {
+                            new_repo.save(&repo_path)?;
+                            exit!(0, screen);
+                        },
+                        KeyEvent::Char('n') | KeyEvent::Char('q') | KeyEvent::Esc => {
+                            review = None;
+                            size = (0, 0);
+                        },
+                        _ => {}
+                    }
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+                continue;
+            }
+            let width = size.0;
+            let per_page = page_size(size.1);
+            let fields = match (tab, server) {
+                (Tab::Settings, _) => vec![Field::RepoName, Field::ClientParameters],
+                (Tab::Servers, Some(i)) => vec![Field::ServerName(i), Field::ServerAddress(i), Field::ServerPort(i), Field::ServerPassword(i), Field::BattlEye(i)],
+                _ => Vec::new(),
+            };
+            let (start, end, legend, rows) = match (tab, server) {
+                (Tab::Mods, _) => {
+                    let start = page * per_page;
+                    let end = std::cmp::min(start + per_page, view.len());
+                    let mut info = format!("  Page {} of {}", page + 1, page_count(view.len(), per_page));
+                    let count = marked.iter().filter(|m| **m).count();
+                    if count != 0 {
+                        info.push_str(&format!("  {} marked", count));
+                    }
+                    if filtering || !filter.is_empty() {
+                        info.push_str(&format!("  /{}{}", filter, if filtering { "_" } else { "" }));
+                    }
+                    let legend = format!("{} - {} - {} - {} - {}{}", "Existing".purple(), "New".green(), "Remove".red(), "Ignored".white(), "Missing".yellow(), pad(&info, width.saturating_sub(43)));
+                    (start, end, legend, Interact::mod_rows(&mods, &view[start..end], start, &marked, width))
+                },
+                (Tab::Servers, None) => {
+                    (0, draft.servers.len(), pad("Enter to edit - Insert to add - Delete to remove", width), Interact::server_rows(&draft, width))
+                },
+                (Tab::Servers, Some(_)) => {
+                    (0, fields.len(), pad("Enter to edit - Esc to go back", width), Interact::field_rows(&draft, &fields, &editing, width))
+                },
+                (Tab::Settings, _) => {
+                    (0, fields.len(), pad("Enter to edit", width), Interact::field_rows(&draft, &fields, &editing, width))
+                },
+            };
+            Interact::tick(&draft, tab, legend, &rows, &selected, size)?;
             if let Some(key_event) = stdin.next() {
-                let cursor = cursor2index(index, mods.len());
+                let cursor = selected;
+                if let Some((field, mut buffer)) = editing.take() {
+                    // Text entry captures every key until it is committed or abandoned
+                    if let InputEvent::Keyboard(e) = key_event {
+                        match e {
+                            KeyEvent::Char('\n') => {
+                                field.set(&mut draft, buffer);
+                                continue;
+                            },
+                            KeyEvent::Esc => continue,
+                            KeyEvent::Backspace => { buffer.pop(); },
+                            KeyEvent::Char(c) if !c.is_control() => buffer.push(c),
+                            _ => {}
+                        }
+                    }
+                    editing = Some((field, buffer));
+                    continue;
+                }
+                if filtering {
+                    // The filter is narrowed as it is typed, Enter keeps it and Esc clears it
+                    if let InputEvent::Keyboard(e) = key_event {
+                        match e {
+                            KeyEvent::Char('\n') => filtering = false,
+                            KeyEvent::Esc => {
+                                filtering = false;
+                                filter.clear();
+                            },
+                            KeyEvent::Backspace => { filter.pop(); },
+                            KeyEvent::Char(c) if !c.is_control() => filter.push(c),
+                            _ => {}
+                        }
+                        page = 0;
+                        selected = if mods.iter().any(|m| m.0.to_lowercase().contains(&filter.to_lowercase())) { Cursor::Item(0) } else { Cursor::Apply };
+                    }
+                    continue;
+                }
                 match key_event {
                     InputEvent::Keyboard(e) => { match e {
                         KeyEvent::Up => {
-                            index = if index % 10 != 0 { index - 1 } else { index };
+                            selected = match selected {
+                                Cursor::Item(i) if i > start => Cursor::Item(i - 1),
+                                Cursor::Apply if end > start => Cursor::Item(end - 1),
+                                Cursor::Cancel => Cursor::Apply,
+                                _ => selected,
+                            };
                         },
                         KeyEvent::Down => {
-                            if cursor != Cursor::Cancel {
-                                index = index + 1;
-                            }
-                            // index = if index - (10 * (index / 10)) != 9 { index + 1 } else { index };
+                            selected = match selected {
+                                Cursor::Item(i) if i + 1 < end => Cursor::Item(i + 1),
+                                Cursor::Item(_) => Cursor::Apply,
+                                _ => Cursor::Cancel,
+                            };
                         },
-                        KeyEvent::Right => {
-                            page = if (page + 1) * 8 < mods.len() - 1 { page + 1 } else { page };
-                            index = page * 10;
-                        }
-                        KeyEvent::Left => {
-                            page = if page == 0 { 0 } else { page - 1};
-                            index = page * 10;
-                        }
-                        KeyEvent::Char(c) => {
-                            match c {
-                                ' ' => {
-                                    if let Cursor::Item(c) = cursor {
-                                        mods[c].2 = match mods[c].2 {
-                                            State::Disabled => State::Enabled,
-                                            State::Enabled => State::OptionalDisabled,
-                                            State::OptionalDisabled => State::OptionalEnabled,
-                                            State::OptionalEnabled => State::Disabled,
-                                        };
+                        KeyEvent::End => {
+                            selected = Cursor::Apply;
+                        },
+                        KeyEvent::Char('\t') => {
+                            tab = tab.next();
+                            server = None;
+                            let items = match tab {
+                                Tab::Mods => view.len(),
+                                Tab::Settings => 2,
+                                Tab::Servers => draft.servers.len(),
+                            };
+                            page = 0;
+                            selected = if items == 0 { Cursor::Apply } else { Cursor::Item(0) };
+                        },
+                        KeyEvent::Char('q') => {
+                            exit!(0, screen);
+                        },
+                        KeyEvent::Char('\n') if cursor == Cursor::Apply => {
+                            let new_repo = Interact::build(&draft, &mods);
+                            let changes = Interact::changes(&repo, &new_repo);
+                            review = Some((new_repo, changes));
+                            size = (0, 0);
+                        },
+                        KeyEvent::Char('\n') if cursor == Cursor::Cancel => {
+                            exit!(0, screen);
+                        },
+                        e => match tab {
+                            Tab::Mods => {
+                                // The mod under the cursor, none when the filter hides every mod
+                                let current = match cursor {
+                                    Cursor::Item(c) => view.get(c).cloned(),
+                                    _ => None,
+                                };
+                                // Actions apply to every marked mod, or the one under the cursor
+                                let targets: Vec<usize> = if marked.contains(&true) {
+                                    (0..mods.len()).filter(|i| marked[*i]).collect()
+                                } else {
+                                    current.into_iter().collect()
+                                };
+                                let action = match e {
+                                    KeyEvent::Char(' ') => Some(Action::Cycle),
+                                    KeyEvent::Char('\n') | KeyEvent::Insert | KeyEvent::Char('e') => Some(Action::Include),
+                                    KeyEvent::Delete => Some(Action::Remove),
+                                    KeyEvent::Char('d') => Some(Action::Disable),
+                                    KeyEvent::Char('o') => Some(Action::Optional),
+                                    _ => None,
+                                };
+                                if let Some(action) = action {
+                                    if !targets.is_empty() {
+                                        undo.push(mods.clone());
+                                        redo.clear();
+                                        for i in targets {
+                                            mods[i] = action.apply(&mods[i], &missing);
+                                        }
                                     }
+                                    continue;
+                                }
+                                match e {
+                                    KeyEvent::Right => {
+                                        if page + 1 < page_count(view.len(), per_page) {
+                                            page += 1;
+                                            selected = Cursor::Item(page * per_page);
+                                        }
+                                    }
+                                    KeyEvent::Left => {
+                                        if page > 0 {
+                                            page -= 1;
+                                            selected = Cursor::Item(page * per_page);
+                                        }
+                                    }
+                                    KeyEvent::Char('m') => {
+                                        if let Some(i) = current {
+                                            marked[i] = !marked[i];
+                                            anchor = i;
+                                        }
+                                    },
+                                    KeyEvent::Char('M') => {
+                                        if let Some(i) = current {
+                                            let (from, to) = if anchor < i { (anchor, i) } else { (i, anchor) };
+                                            for i in view.iter().filter(|i| **i >= from && **i <= to) {
+                                                marked[*i] = true;
+                                            }
+                                        }
+                                    },
+                                    KeyEvent::Char('a') => {
+                                        let all = view.iter().all(|i| marked[*i]);
+                                        for i in &view {
+                                            marked[*i] = !all;
+                                        }
+                                    },
+                                    KeyEvent::Char('/') => {
+                                        filtering = true;
+                                    },
+                                    KeyEvent::Char('u') | KeyEvent::Ctrl('z') => {
+                                        if let Some(previous) = undo.pop() {
+                                            redo.push(std::mem::replace(&mut mods, previous));
+                                        }
+                                    },
+                                    KeyEvent::Ctrl('r') | KeyEvent::Ctrl('y') => {
+                                        if let Some(next) = redo.pop() {
+                                            undo.push(std::mem::replace(&mut mods, next));
+                                        }
+                                    },
+                                    _ => {}
+                                }
+                            },
+                            Tab::Settings => {
+                                if let (KeyEvent::Char('\n'), Cursor::Item(c)) = (e, cursor) {
+                                    editing = Some((fields[c], fields[c].value(&draft)));
+                                }
+                            },
+                            Tab::Servers => match (e, cursor, server) {
+                                (KeyEvent::Char('\n'), Cursor::Item(c), None) => {
+                                    server = Some(c);
+                                    selected = Cursor::Item(0);
                                 },
-                                'q' => {
-                                    exit!(0, screen);
+                                (KeyEvent::Insert, _, None) => {
+                                    draft.servers.push(Server {
+                                        name: format!("Server {}", draft.servers.len() + 1),
+                                        address: String::new(),
+                                        port: "2302".to_owned(),
+                                        password: String::new(),
+                                        battleEye: true,
+                                    });
+                                    server = Some(draft.servers.len() - 1);
+                                    selected = Cursor::Item(0);
                                 },
-                                '\n' => {
-                                    match cursor {
-                                        Cursor::Item(c) => {
-                                            mods[c].1 = if mods[c].1 == Transaction::Remove || mods[c].1 == Transaction::Existing { Transaction::Existing } else { Transaction::Add };
-                                            mods[c].2 = State::Enabled;
-                                        },
-                                        Cursor::Apply => {
-                                            repo.requiredMods = Vec::new();
-                                            repo.optionalMods = Vec::new();
-                                            for arma_mod in mods {
-                                                if arma_mod.1 == Transaction::Add || arma_mod.1 == Transaction::Existing {
-                                                    let new_mod = Mod {
-                                                        modName: arma_mod.0,
-                                                        checkSum: "".to_owned(),
-                                                        Enabled: arma_mod.2 == State::Enabled || arma_mod.2 == State::OptionalEnabled,
-                                                    };
-                                                    match arma_mod.2 {
-                                                        State::Enabled | State::Disabled => {
-                                                            repo.requiredMods.push(new_mod);
-                                                        },
-                                                        State::OptionalDisabled | State::OptionalEnabled => {
-                                                            repo.optionalMods.push(new_mod);
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                            let j = serde_json::to_string_pretty(&repo).unwrap();
-                                            let mut fout = File::create(repo_path).unwrap();
-                                            fout.write_all(j.as_bytes()).unwrap();
-                                            exit!(0, screen);
-                                        },
-                                        Cursor::Cancel => {exit!(0, screen);},
+                                (KeyEvent::Delete, Cursor::Item(c), None) => {
+                                    draft.servers.remove(c);
+                                    if draft.servers.is_empty() {
+                                        selected = Cursor::Apply;
+                                    } else if c == draft.servers.len() {
+                                        selected = Cursor::Item(c - 1);
                                     }
                                 },
+                                (KeyEvent::Char('\n'), Cursor::Item(c), Some(_)) => {
+                                    if let Field::BattlEye(i) = fields[c] {
+                                        draft.servers[i].battleEye = !draft.servers[i].battleEye;
+                                    } else {
+                                        editing = Some((fields[c], fields[c].value(&draft)));
+                                    }
+                                },
+                                (KeyEvent::Char(' '), Cursor::Item(c), Some(_)) => {
+                                    if let Field::BattlEye(i) = fields[c] {
+                                        draft.servers[i].battleEye = !draft.servers[i].battleEye;
+                                    }
+                                },
+                                (KeyEvent::Esc, _, Some(i)) | (KeyEvent::Left, _, Some(i)) => {
+                                    server = None;
+                                    selected = Cursor::Item(i);
+                                },
                                 _ => {}
-                            }
-                        }
-                        KeyEvent::Delete => {
-                            if let Cursor::Item(c) = cursor {
-                                let current = mods[c].1.clone();
-                                mods[c].1 = match current {
-                                    Transaction::Existing => Transaction::Remove,
-                                    Transaction::Add => Transaction::Ignore,
-                                    _ => current,
-                                };
-                                mods[c].2 = State::Disabled;
-                            }
-                        }
-                        KeyEvent::Insert => {
-                            if let Cursor::Item(c) = cursor {
-                                mods[c].1 = if mods[c].1 == Transaction::Remove || mods[c].1 == Transaction::Existing { Transaction::Existing } else { Transaction::Add };
-                                mods[c].2 = State::Enabled;
-                            }
-                        }
-                        KeyEvent::End => {
-                            index = (10 * (index / 10)) + 8;
+                            },
                         },
-                        _ => {}
                     }},
                     _ => {}
                 }
@@ -190,26 +535,173 @@ impl Command for Interact {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Tab {
+    Mods,
+    Settings,
+    Servers,
+}
+
+impl Tab {
+    fn next(self) -> Tab {
+        match self {
+            Tab::Mods => Tab::Settings,
+            Tab::Settings => Tab::Servers,
+            Tab::Servers => Tab::Mods,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Tab::Mods => "Mods",
+            Tab::Settings => "Settings",
+            Tab::Servers => "Servers",
+        }
+    }
+}
+
+/// A change to the state of one or more mods
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Action {
+    Cycle,
+    Include,
+    Remove,
+    Disable,
+    Optional,
+}
+
+impl Action {
+    fn apply(self, arma_mod: &(String, Transaction, State), missing: &[String]) -> (String, Transaction, State) {
+        let (name, trans, state) = arma_mod.clone();
+        match self {
+            Action::Cycle => (name, trans, match state {
+                State::Disabled => State::Enabled,
+                State::Enabled => State::OptionalDisabled,
+                State::OptionalDisabled => State::OptionalEnabled,
+                State::OptionalEnabled => State::Disabled,
+            }),
+            Action::Include => (name, include(arma_mod, missing), State::Enabled),
+            Action::Remove => (name, match trans {
+                Transaction::Existing | Transaction::Missing => Transaction::Remove,
+                Transaction::Add => Transaction::Ignore,
+                _ => trans,
+            }, State::Disabled),
+            Action::Disable => (name, trans, State::Disabled),
+            Action::Optional => (name, include(arma_mod, missing), State::OptionalEnabled),
+        }
+    }
+}
+
+/// An editable value of the repository, servers are referenced by index
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Field {
+    RepoName,
+    ClientParameters,
+    ServerName(usize),
+    ServerAddress(usize),
+    ServerPort(usize),
+    ServerPassword(usize),
+    BattlEye(usize),
+}
+
+impl Field {
+    fn label(self) -> &'static str {
+        match self {
+            Field::RepoName => "Name",
+            Field::ClientParameters => "Client Parameters",
+            Field::ServerName(_) => "Name",
+            Field::ServerAddress(_) => "Address",
+            Field::ServerPort(_) => "Port",
+            Field::ServerPassword(_) => "Password",
+            Field::BattlEye(_) => "BattlEye",
+        }
+    }
+
+    fn value(self, repo: &Repo) -> String {
+        match self {
+            Field::RepoName => repo.repoName.clone(),
+            Field::ClientParameters => repo.clientParameters.clone(),
+            Field::ServerName(i) => repo.servers[i].name.clone(),
+            Field::ServerAddress(i) => repo.servers[i].address.clone(),
+            Field::ServerPort(i) => repo.servers[i].port.clone(),
+            Field::ServerPassword(i) => repo.servers[i].password.clone(),
+            Field::BattlEye(i) => if repo.servers[i].battleEye { "Yes" } else { "No" }.to_owned(),
+        }
+    }
+
+    /// Value as shown in the list, passwords are masked
+    fn display(self, repo: &Repo) -> String {
+        match self {
+            Field::ServerPassword(i) => "*".repeat(repo.servers[i].password.chars().count()),
+            _ => self.value(repo),
+        }
+    }
+
+    fn set(self, repo: &mut Repo, value: String) {
+        match self {
+            Field::RepoName => repo.repoName = value,
+            Field::ClientParameters => repo.clientParameters = value,
+            Field::ServerName(i) => repo.servers[i].name = value,
+            Field::ServerAddress(i) => repo.servers[i].address = value,
+            Field::ServerPort(i) => repo.servers[i].port = value,
+            Field::ServerPassword(i) => repo.servers[i].password = value,
+            Field::BattlEye(i) => repo.servers[i].battleEye = value == "Yes",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Cursor {
     Item(usize),
     Apply,
     Cancel,
 }
 
-fn cursor2index(c: usize, max: usize) -> Cursor {
-    match c - (10 * (c / 10)) {
-        8 => Cursor::Apply,
-        9 => Cursor::Cancel,
-        _ => {
-            let i = c - (2 * (c / 10));
-            if i == max {
-                Cursor::Apply
-            } else if i == max + 1 {
-                Cursor::Cancel
-            } else {
-                Cursor::Item(i)
-            }
+/// Number of mods that fit on one page of a terminal with the given height
+fn page_size(height: usize) -> usize {
+    std::cmp::max(1, height.saturating_sub(RESERVED_LINES))
+}
+
+fn page_count(mods: usize, per_page: usize) -> usize {
+    std::cmp::max(1, (mods + per_page - 1) / per_page)
+}
+
+/// Width of the name column, wide enough for the longest name if the terminal allows it
+fn name_width(mods: &[(String, Transaction, State)], width: usize) -> usize {
+    let longest = mods.iter().map(|m| m.0.chars().count()).max().unwrap_or(0);
+    let available = width.saturating_sub(STATE_WIDTH + 3);
+    std::cmp::max(1, std::cmp::min(longest, available))
+}
+
+/// Pad or truncate `text` to exactly `width` characters
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() > width {
+        let mut out: String = text.chars().take(width.saturating_sub(1)).collect();
+        out.push('~');
+        out
+    } else {
+        pad(text, width)
+    }
+}
+
+/// Transaction for a mod that is being added back into the repository
+fn include(arma_mod: &(String, Transaction, State), missing: &[String]) -> Transaction {
+    match arma_mod.1 {
+        Transaction::Remove | Transaction::Existing | Transaction::Missing => {
+            if missing.contains(&arma_mod.0) { Transaction::Missing } else { Transaction::Existing }
         },
+        _ => Transaction::Add,
+    }
+}
+
+/// Like `state!`, but names the disabled state instead of leaving it blank
+fn describe(state: &State) -> &'static str {
+    match state {
+        State::Disabled => "Disabled",
+        _ => state!(state),
     }
 }
+
+fn pad(text: &str, width: usize) -> String {
+    format!("{:width$}", text, width = width)
+}
@@ -0,0 +1,31 @@
+use colored::*;
+
+use crate::project::Settings;
+use crate::lint::{self, Level};
+use crate::{Command, SmoothlyError};
+
+pub struct Lint {}
+
+impl Command for Lint {
+    fn register(&self) -> clap::App {
+        clap::SubCommand::with_name("lint")
+            .about("Check the repository for problems")
+    }
+
+    fn run(&self, _: &clap::ArgMatches, repo: String, _: &Settings) -> Result<(), SmoothlyError> {
+        let issues = lint::lint(&repo)?;
+        for issue in &issues {
+            match issue.level {
+                Level::Error => println!("{} {}", "error:".red(), issue.message),
+                Level::Warning => println!("{} {}", "warning:".yellow(), issue.message),
+            }
+        }
+        let errors = issues.iter().filter(|i| i.level == Level::Error).count();
+        let warnings = issues.len() - errors;
+        if errors != 0 {
+            return Err(SmoothlyError::GENERIC(format!("{} errors, {} warnings", errors, warnings)));
+        }
+        println!("{} errors, {} warnings", errors, warnings);
+        Ok(())
+    }
+}
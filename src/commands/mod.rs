@@ -1,25 +1,102 @@
+use std::path::PathBuf;
+
+use crate::project::Settings;
 use crate::SmoothlyError;
 
 pub trait Command {
     // (name, description)
     fn register(&self) -> clap::App;
-    
-    fn run(&self, _: &clap::ArgMatches, _: String) -> Result<(), SmoothlyError> {
+
+    // Whether the command operates on a repo.json
+    fn needs_repo(&self) -> bool {
+        true
+    }
+
+    fn run(&self, _: &clap::ArgMatches, _: String, _: &Settings) -> Result<(), SmoothlyError> {
         unimplemented!();
     }
 }
 
+/// Arguments of commands working on an output directory, to go before their other positionals
+fn dir_args<'a, 'b>() -> Vec<clap::Arg<'a, 'b>> {
+    vec![
+        clap::Arg::with_name("dir")
+            .help("Output directory, left out when smoothly.toml sets `output`"),
+        clap::Arg::with_name("other-dir")
+            .help("Output directory to use instead of the one from smoothly.toml")
+            .long("dir")
+            .takes_value(true),
+    ]
+}
+
+/// Split the positionals of a command registered with `dir_args`
+///
+/// The output directory is the first positional, unless smoothly.toml sets one. Then every
+/// positional belongs to the command, so `push @ace` pushes @ace to the configured output.
+/// Returns the output directory and the values for `names`, in order. The first `required`
+/// names must be given, and the last one takes any number of values when `variadic`.
+fn positionals(args: &clap::ArgMatches, settings: &Settings, names: &[&str], required: usize, variadic: bool) -> Result<(PathBuf, Vec<String>), SmoothlyError> {
+    let mut values: Vec<String> = Vec::new();
+    for name in std::iter::once(&"dir").chain(names) {
+        if let Some(v) = args.values_of(name) {
+            values.extend(v.map(|s| s.to_owned()));
+        }
+    }
+    let dir = match (args.value_of("other-dir"), &settings.output) {
+        (Some(dir), _) => dir.to_owned(),
+        (None, Some(output)) => output.clone(),
+        (None, None) if values.is_empty() => {
+            return Err(SmoothlyError::GENERIC("No output directory given, pass one or set `output` in smoothly.toml".to_owned()));
+        },
+        (None, None) => values.remove(0),
+    };
+    if values.len() < required {
+        return Err(SmoothlyError::GENERIC(format!("Missing <{}>", names[values.len()])));
+    }
+    if values.len() > names.len() && !variadic {
+        return Err(SmoothlyError::GENERIC(format!("Unexpected argument `{}`", values[names.len()])));
+    }
+    Ok((PathBuf::from(dir), values))
+}
+
 mod new;
 pub use new::New;
 
 mod add;
 pub use add::Add;
 
+mod changelog;
+pub use changelog::Changelog;
+
+mod conflicts;
+pub use conflicts::Conflicts;
+
+mod dedupe;
+pub use dedupe::Dedupe;
+
+mod deps;
+pub use deps::Deps;
+
 mod interact;
 pub use interact::Interact;
 
+mod lint;
+pub use lint::Lint;
+
 mod push;
 pub use push::Push;
 
+mod rollback;
+pub use rollback::Rollback;
+
+mod serve;
+pub use serve::Serve;
+
+mod size;
+pub use size::Size;
+
+mod snapshots;
+pub use snapshots::Snapshots;
+
 mod selfupdate;
 pub use selfupdate::SelfUpdate;
\ No newline at end of file
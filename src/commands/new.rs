@@ -5,6 +5,7 @@ use std::path::PathBuf;
 use question::{Question, Answer};
 use serde_json;
 
+use crate::project::Settings;
 use crate::{SmoothlyError, Command, Repo, Server};
 
 pub struct New {}
@@ -19,7 +20,7 @@ impl Command for New {
             )
     }
 
-    fn run(&self, args: &clap::ArgMatches, repopath: String) -> Result<(), SmoothlyError> {
+    fn run(&self, args: &clap::ArgMatches, repopath: String, _: &Settings) -> Result<(), SmoothlyError> {
         let repo = Repo {
             repoName: if let Some(name) = args.value_of("name") {
                 name.to_owned()
@@ -1,252 +1,494 @@
 use std::path::PathBuf;
-use std::ffi::OsStr;
 use std::fs::File;
 use std::io::{Read, Write};
+use std::time::Duration;
 
-use md5::{Md5, Digest};
-use pbo::PBO;
+use colored::*;
+use md5::Digest;
 use sha1::{Sha1};
 
-use crate::{SmoothlyError, Command, Repo, Addon, SwiftyFile, FilePart};
+use crate::{conflicts, deps, hooks, link, notify, png, progress, publish, snapshot, srf, version, SmoothlyError, Command, Repo, LinkMode};
+use crate::lock::Lock;
+use crate::project::Settings;
+use crate::progress::Progress;
+use crate::watch::{Change, Watch};
 
 pub struct Push {}
 
 impl Command for Push {
     fn register(&self) -> clap::App {
-        clap::SubCommand::with_name("push")
+        let mut app = clap::SubCommand::with_name("push")
             .about("Push the mods to an output directory")
-            .arg(clap::Arg::with_name("dir")
-                .help("Output directory")
-                .required(true)
-            ).arg(clap::Arg::with_name("mods")
+            .args(&super::dir_args())
+            .arg(clap::Arg::with_name("mods")
                 .help("Mods to push")
                 .multiple(true)
                 .takes_value(true)
-            )
+            ).arg(clap::Arg::with_name("missing")
+                .help("What to do with mods in repo.json that have no folder in basePath")
+                .long("missing")
+                .takes_value(true)
+                .possible_values(&["error", "keep", "drop"])
+                .default_value("error")
+            ).arg(clap::Arg::with_name("with")
+                .help("Additional repositories sharing the same basePath, as `path` or `path=published.json`")
+                .long("with")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            ).arg(clap::Arg::with_name("link-mode")
+                .help("How mod files are placed in the output directory")
+                .long("link-mode")
+                .takes_value(true)
+                .possible_values(&["copy", "hardlink", "reflink"])
+                .default_value("copy")
+            ).arg(clap::Arg::with_name("keep-snapshots")
+                .help("Number of published snapshots to keep, 0 to disable them")
+                .long("keep-snapshots")
+                .takes_value(true)
+                .default_value("10")
+            ).arg(clap::Arg::with_name("webhook")
+                .help("URL to notify after a successful push, Discord compatible")
+                .long("webhook")
+                .takes_value(true)
+                .env("SMOOTHLY_WEBHOOK")
+            ).arg(clap::Arg::with_name("webhook-retries")
+                .help("Number of times to retry a failed notification")
+                .long("webhook-retries")
+                .takes_value(true)
+                .default_value("3")
+            ).arg(clap::Arg::with_name("no-notify")
+                .help("Don't notify the webhook")
+                .long("no-notify")
+            ).arg(clap::Arg::with_name("resize-image")
+                .help("Convert repo.png to 300x160 when it has other dimensions or format")
+                .long("resize-image")
+            ).arg(clap::Arg::with_name("format-version")
+                .help("repo.json format to publish")
+                .long("format-version")
+                .takes_value(true)
+                .possible_values(version::KNOWN)
+                .default_value(version::CURRENT)
+            ).arg(clap::Arg::with_name("published-base-path")
+                .help("basePath written to the published repository, empty by default so the local one isn't leaked")
+                .long("published-base-path")
+                .takes_value(true)
+            ).arg(clap::Arg::with_name("omit-passwords")
+                .help("Publish the servers without their passwords")
+                .long("omit-passwords")
+            ).arg(clap::Arg::with_name("generate-image")
+                .help("Generate a placeholder when there is no repo.png")
+                .long("generate-image")
+            ).arg(clap::Arg::with_name("ignore")
+                .help("Files and folders in the mods not to push, such as `*.bak` or `.git`")
+                .long("ignore")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            ).arg(clap::Arg::with_name("wait")
+                .help("Wait for another command using the output directory instead of failing")
+                .long("wait")
+            ).arg(clap::Arg::with_name("watch")
+                .help("Keep running and push the mods again when their files change")
+                .long("watch")
+            ).arg(clap::Arg::with_name("debounce")
+                .help("Milliseconds without changes to wait for before pushing again")
+                .long("debounce")
+                .takes_value(true)
+                .default_value("1000")
+            );
+        for hook in hooks::ALL {
+            app = app.arg(clap::Arg::with_name(*hook)
+                .help("Command to run at this point of the push, a failing pre-push aborts it")
+                .long(*hook)
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            );
+        }
+        app
     }
 
-    fn run(&self, args: &clap::ArgMatches, repo: String) -> Result<(), SmoothlyError> {
-        let repo = Repo::new(repo)?;
-        let dir = args.value_of("dir").unwrap();
-        if !PathBuf::from(&dir).exists() {
-            std::fs::create_dir_all(&dir)?;
+    fn run(&self, args: &clap::ArgMatches, repo: String, settings: &Settings) -> Result<(), SmoothlyError> {
+        let (dir, given) = super::positionals(args, settings, &["mods"], 0, true)?;
+        let dir = dir.display().to_string();
+        // With a configured output every positional is a mod, a directory given out of habit
+        // would otherwise push nothing and still report success
+        if !given.is_empty() {
+            let repos = repos(args, &repo)?;
+            if let Some(unknown) = given.iter().find(|m| !repos.iter().any(|(_, r)| r.has_mod(m))) {
+                return Err(SmoothlyError::GENERIC(format!("`{}` is not a mod in the pushed repositories, use --dir to choose the output directory", unknown)));
+            }
         }
-
         let repofile = format!("{}{}repo.json", dir, std::path::MAIN_SEPARATOR);
-        let mods: Vec<String> = if args.is_present("mods") {
+        let mods: Vec<String> = if !given.is_empty() {
             if !PathBuf::from(&repofile).exists() {
                 println!("Unable to use selective push when no repo.json file exists");
                 vec!()
             } else {
-                args.values_of("mods").unwrap().map(|s| s.to_owned()).collect()
+                given
             }
         } else {
             vec!()
         };
-        
-        let mut outrepo = repo.clone();
-        if !PathBuf::from(&repofile).exists() {
-            File::create(&repofile)?;
+
+        self.push(args, &dir, &repo, &mods)?;
+        if !args.is_present("watch") {
+            return Ok(());
+        }
+
+        let window = Duration::from_millis(args.value_of("debounce").unwrap().parse()
+            .map_err(|_| SmoothlyError::GENERIC("--debounce must be a number".to_owned()))?);
+        let base = Repo::new(repo.clone())?.basePath;
+        let watch = Watch::new(&PathBuf::from(&base), &PathBuf::from(&repo), window)?;
+        loop {
+            println!("Watching {} for changes", base);
+            let mods = match watch.wait()? {
+                Change::All => {
+                    println!("Pushing everything again");
+                    vec!()
+                },
+                Change::Mods(mods) => {
+                    println!("Pushing {} again", mods.join(", "));
+                    mods
+                },
+            };
+            // Keep watching after a failed push, the next change may fix it
+            if let Err(e) = self.push(args, &dir, &repo, &mods) {
+                println!("{}", e);
+            }
+        }
+    }
+}
+
+impl Push {
+    /// Push `mods`, or everything when it is empty
+    fn push(&self, args: &clap::ArgMatches, dir: &str, repo_file: &str, mods: &[String]) -> Result<(), SmoothlyError> {
+        if !PathBuf::from(&dir).exists() {
+            std::fs::create_dir_all(&dir)?;
+        }
+        let _lock = Lock::acquire(&PathBuf::from(dir), args.is_present("wait"))?;
+
+        let repos = repos(args, repo_file)?;
+        let repo = repos[0].1.clone();
+
+        let mut pushing: Vec<String> = mods.to_vec();
+        if pushing.is_empty() {
+            for (_, r) in &repos {
+                pushing.extend(r.mods().map(|m| m.modName.clone()).filter(|m| !pushing.contains(m)).collect::<Vec<_>>());
+            }
+        }
+        let env = vec![
+            ("SMOOTHLY_REPO".to_owned(), repo_file.to_owned()),
+            ("SMOOTHLY_REPO_NAME".to_owned(), repo.repoName.clone()),
+            ("SMOOTHLY_BASE_PATH".to_owned(), repo.basePath.clone()),
+            ("SMOOTHLY_OUTPUT".to_owned(), dir.to_owned()),
+            ("SMOOTHLY_MODS".to_owned(), pushing.join("\n")),
+        ];
+        hook(args, hooks::PRE_PUSH, &env)?;
+
+        // The state clients currently see, to describe the changes afterwards
+        let previous = srf::Published::load(&PathBuf::from(dir), "repo.json").ok();
+
+        let mut outrepos: Vec<(String, Repo)> = repos.clone();
+
+        for (_, outrepo) in &mut outrepos {
+            let missing = outrepo.missing_mods();
+            if missing.is_empty() {
+                continue;
+            }
+            let action = args.value_of("missing").unwrap();
+            println!("{} mods in {} have no folder in {}", missing.len(), outrepo.repoName, outrepo.basePath);
+            for name in &missing {
+                println!(" - {}", name);
+            }
+            match action {
+                "keep" => {
+                    for name in &missing {
+                        if !PathBuf::from(&dir).join(name).is_dir() {
+                            println!("Warning: {} has not been pushed before and will not be downloadable", name);
+                        }
+                    }
+                },
+                "drop" => {
+                    for name in &missing {
+                        outrepo.remove_mod(name);
+                    }
+                    println!("Dropping them from the published repository");
+                },
+                _ => {
+                    return Err(SmoothlyError::GENERIC("Use `--missing keep` or `--missing drop` to push anyway".to_owned()));
+                },
+            }
+        }
+        if !args.is_present("omit-passwords") {
+            for (_, outrepo) in &outrepos {
+                publish::check(outrepo)?;
+            }
+        }
+        for (file, _) in &outrepos {
+            let repofile = format!("{}{}{}", dir, std::path::MAIN_SEPARATOR, file);
+            if !PathBuf::from(&repofile).exists() {
+                File::create(&repofile)?;
+            }
         }
 
         let srcimage = format!("{}{}repo.png", repo.basePath, std::path::MAIN_SEPARATOR);
+        let dst = PathBuf::from(format!("{}{}repo.png", dir, std::path::MAIN_SEPARATOR));
         if !PathBuf::from(&srcimage).exists() {
-            println!("A repo.png is required. Add it to {}{} with dimensions of 300x160", repo.basePath, std::path::MAIN_SEPARATOR);
-            std::process::exit(1);
+            if !args.is_present("generate-image") {
+                // Returned rather than exiting, so the lock is released and --watch keeps going
+                return Err(SmoothlyError::GENERIC(format!("A repo.png is required. Add it to {}{} with dimensions of {}x{}, or use --generate-image", repo.basePath, std::path::MAIN_SEPARATOR, png::WIDTH, png::HEIGHT)));
+            }
+            println!("Generating a placeholder repo.png");
+            link::create(&dst)?;
+            png::placeholder(&repo.repoName, &dst)?;
         } else {
-            let dst = format!("{}{}repo.png", dir, std::path::MAIN_SEPARATOR);
-            std::fs::copy(srcimage, &dst)?;
+            let valid = match png::dimensions(&std::fs::read(&srcimage)?) {
+                Ok((w, h)) if w == png::WIDTH && h == png::HEIGHT => Ok(()),
+                Ok((w, h)) => Err(format!("repo.png is {}x{}, it must be {}x{}", w, h, png::WIDTH, png::HEIGHT)),
+                Err(e) => Err(e.to_string()),
+            };
+            match valid {
+                Ok(_) => { link::transfer(&PathBuf::from(&srcimage), &dst, LinkMode::Copy)?; },
+                Err(e) => {
+                    if !args.is_present("resize-image") {
+                        return Err(SmoothlyError::GENERIC(format!("{}, use --resize-image to convert it while pushing", e)));
+                    }
+                    println!("{}, resizing", e);
+                    link::create(&dst)?;
+                    png::resize(&PathBuf::from(&srcimage), &dst)?;
+                },
+            }
+        }
+        {
             let mut image = File::open(&dst)?;
             let mut data = Vec::new();
             image.read_to_end(&mut data)?;
             let mut hasher = Sha1::new();
             hasher.input(&data);
-            outrepo.imageChecksum = format!("{:X}", hasher.result());
+            let checksum = format!("{:X}", hasher.result());
+            for (_, outrepo) in &mut outrepos {
+                outrepo.imageChecksum = checksum.clone();
+            }
         }
 
-        let mut options = fs_extra::dir::CopyOptions::new();
-        options.overwrite = true;
-        options.skip_exist = false;
+        let link_mode = LinkMode::from_name(args.value_of("link-mode").unwrap())?;
+        let ignore: Vec<String> = args.values_of("ignore").map(|v| v.map(|s| s.to_owned()).collect()).unwrap_or_default();
+        let mut fallback = false;
 
         println!("Transfering files");
 
+        let mut transfers = Vec::new();
         for entry in std::fs::read_dir(&repo.basePath)? {
             let entry = entry?;
             let path = entry.path();
             if !path.is_dir() { continue; }
             let name = path.file_name().unwrap().to_str().unwrap().to_owned();
             if !mods.is_empty() && !mods.contains(&name) { continue; }
-            println!(" - {}", name);
-            if repo.has_mod(&name) {
+            if repos.iter().any(|(_, r)| r.has_mod(&name)) {
+                transfers.push((name, path));
+            }
+        }
+        let sizes: Vec<u64> = transfers.iter().map(|(_, path)| progress::size(path)).collect();
+        {
+            let progress = Progress::new("Copying", sizes.iter().sum());
+            for ((name, path), size) in transfers.iter().zip(sizes) {
+                progress.start_mod(name, size);
                 let moddir = &format!("{}{}{}", dir, std::path::MAIN_SEPARATOR, name);
-                std::fs::remove_dir_all(&moddir)?;
-                std::fs::create_dir_all(&moddir)?;
-                fs_extra::copy_items(&vec!(path), &format!("{}{}", dir, std::path::MAIN_SEPARATOR), &options).unwrap();
+                // Removing the old copy first means linked files are never written through
+                if PathBuf::from(&moddir).exists() {
+                    std::fs::remove_dir_all(&moddir)?;
+                }
+                let used = link::transfer_dir(&path, &PathBuf::from(&moddir), link_mode, &ignore, &progress)?;
+                if used != link_mode && !fallback {
+                    progress.println(&format!("Unable to use {:?} links in {}, copying instead", link_mode, dir));
+                    fallback = true;
+                }
+                let mut env = env.clone();
+                env.push(("SMOOTHLY_MOD".to_owned(), name.clone()));
+                env.push(("SMOOTHLY_MOD_DIR".to_owned(), moddir.clone()));
+                warn(hook(args, hooks::POST_COPY, &env));
             }
         }
 
         println!("Generating SRFs");
 
+        let mut candidates = Vec::new();
+        for (_, outrepo) in &outrepos {
+            candidates.extend(conflicts::candidates(outrepo));
+        }
+        let mut pbos = Vec::new();
+        let mut patches = Vec::new();
+
+        let mut generates = Vec::new();
         for entry in std::fs::read_dir(dir)? {
             let entry = entry?;
             let path = entry.path();
             if !path.is_dir() { continue; }
             let name = path.file_name().unwrap().to_str().unwrap().to_owned();
+            if name == snapshot::STATE_DIR { continue; }
             if !mods.is_empty() && !mods.contains(&name) { continue; }
-            println!(" - {}", name);
-            let mut addon = Addon::new(name.clone());
-            let moddir = &format!("{}{}{}", dir, std::path::MAIN_SEPARATOR, name);
-            if repo.has_mod(&name) {
-                for direntry in walkdir::WalkDir::new(&moddir).sort_by(|a,b| {
-                    if a.path().is_dir() == b.path().is_dir() {
-                        b.file_name().cmp(a.file_name())
-                    } else {
-                        b.path().is_dir().cmp(&a.path().is_dir())
-                    }}) {
-                    let entry = direntry.unwrap();
-                    let path = entry.path();
-                    if path.is_dir() { continue; }
-                    let mut name = String::new();
-                    let mut components = path.components();
-                    components.next();components.next();
-                    components.for_each(|e| {
-                        match e {
-                            std::path::Component::Normal(c) => {
-                                name.push_str(&format!("{}\\", c.to_str().unwrap()));
-                            },
-                            _ => {}
-                        }
-                    });
-                    name.pop();
-                    let mut swiftyfile = SwiftyFile::new(name);
-                    if path.extension().unwrap_or_else(|| OsStr::new("")) == OsStr::new("pbo") {
-                        let pbo = PBO::read(&mut File::open(path).unwrap()).unwrap();
-                        let mut headertotal = Vec::new();
-                        headertotal.append(&mut vec!{0});
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0x5665_7273).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
-
-                        for header in pbo.extension_order {
-                            headertotal.append(&mut header.chars().map(|c| c as u8).collect::<Vec<u8>>());
-                            headertotal.append(&mut vec!{0});
-                            headertotal.append(&mut pbo.extensions.get(&header).unwrap().chars().map(|c| c as u8).collect::<Vec<u8>>());
-                            headertotal.append(&mut vec!{0});
-                        }
-                        headertotal.append(&mut vec!{0});
-                        for header in pbo.headers {
-                            // 4 bytes * 5 u32 per header
-                            headertotal.append(&mut header.filename.chars().map(|c| c as u8).collect::<Vec<u8>>());
-                            headertotal.append(&mut vec!{0});
-                            headertotal.append(&mut transform_u32_to_array_of_u8(header.method).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                            headertotal.append(&mut transform_u32_to_array_of_u8(header.original).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                            headertotal.append(&mut transform_u32_to_array_of_u8(header.reserved).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                            headertotal.append(&mut transform_u32_to_array_of_u8(header.timestamp).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                            headertotal.append(&mut transform_u32_to_array_of_u8(header.size).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                        }
-                        headertotal.append(&mut vec!{0});
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
-                        headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
-
-                        let header_digest = {
-                            let mut hasher = Md5::new();
-                            hasher.input(&headertotal);
-                            hasher.result()
-                        };
-
-                        swiftyfile.parts.push(FilePart {
-                            name: "$$HEADER$$".to_owned(),
-                            size: headertotal.len(),
-                            hash: format!("{:X}", header_digest),
-                            start: 0,
-                        });
-
-                        let mut start = headertotal.len();
-
-                        for mut file in pbo.files {
-                            let mut buffersize = 0;
-                            let mut hasher = Md5::new();
-                            loop {
-                                let mut buffer = vec![0u8; 4194304];
-                                let read = file.1.read(&mut buffer).unwrap();
-                                hasher.input(&buffer[0..read]);
-                                buffersize += read;
-                                if read != 4194304 {
-                                    break;
-                                }
-                            }
-                            swiftyfile.parts.push(FilePart {
-                                name: file.0.clone(),
-                                size: buffersize,
-                                hash: format!("{:X}",hasher.result()),
-                                start,
-                            });
-                            start += buffersize;
-                        }
-                        let mut chk = pbo.checksum.unwrap();
-                        chk.insert(0, 0);
-                        swiftyfile.parts.push(FilePart {
-                            name: "$$END$$".to_owned(),
-                            size:  21,
-                            hash: format!("{:X}", {
-                                    let mut hasher = Md5::new();
-                                    hasher.input(&chk);
-                                    hasher.result()
-                                }),
-                            start,
-                        });
-                    } else {
-                        if path.file_name().unwrap().to_str().unwrap() == "mod.srf" { continue; }
-                        let mut f = File::open(path).unwrap();
-                        let mut buffersize = 0;
-                        let mut hasher = Md5::new();
-                        loop {
-                            let mut buffer = vec![0u8; 4194304];
-                            let read = f.read(&mut buffer).unwrap();
-                            hasher.input(&buffer[0..read]);
-                            buffersize += read;
-                            if read != 4194304 {
-                                break;
-                            }
-                        }
-                        swiftyfile.parts.push(FilePart {
-                            name: format!("{}_{}", path.file_name().unwrap().to_str().unwrap().to_owned(), buffersize),
-                            hash: format!("{:X}", hasher.result()),
-                            size: buffersize as usize,
-                            start: 0,
-                        });
+            if outrepos.iter().any(|(_, r)| r.has_mod(&name)) {
+                generates.push((name, path));
+            }
+        }
+        let sizes: Vec<u64> = generates.iter().map(|(_, path)| progress::size(path)).collect();
+        {
+            let progress = Progress::new("Hashing", sizes.iter().sum());
+            for ((name, path), size) in generates.iter().zip(sizes) {
+                progress.start_mod(name, size);
+                let mut addon = srf::generate(&path, &name, &mut |file, pbo| {
+                    if candidates.contains(name) {
+                        pbos.extend(conflicts::Pbo::new(&name, file, pbo));
+                        patches.append(&mut deps::patches(&name, pbo));
                     }
-                    addon.files.push(swiftyfile);
-                }
+                }, &progress)?;
 
-                let mut outfile = File::create(format!("{}{}mod.srf", moddir, std::path::MAIN_SEPARATOR))?;
+                let mut outfile = link::create(&path.join("mod.srf"))?;
                 outfile.write_all(addon.line().as_bytes())?;
                 for file in &mut addon.files {
                     outfile.write_all(file.line().as_bytes())?;
                 }
-                outrepo.set_hash(&name, addon.hash());
+                let hash = addon.hash();
+                for (_, outrepo) in &mut outrepos {
+                    outrepo.set_hash(&name, hash.clone());
+                }
+            }
+        }
+
+        // A selective push only hashes the mods it pushed, the others keep what is already published
+        if !mods.is_empty() {
+            for (_, outrepo) in &mut outrepos {
+                let others: Vec<String> = outrepo.mods().map(|m| m.modName.clone()).filter(|m| !mods.contains(m)).collect();
+                for name in others {
+                    let hash = match srf::read(&PathBuf::from(dir).join(&name).join("mod.srf")) {
+                        Ok(mut addon) => Some(addon.hash()),
+                        Err(_) => previous.as_ref()
+                            .and_then(|p| p.repo.mods().find(|m| m.modName == name).map(|m| m.checkSum.clone())),
+                    };
+                    if let Some(hash) = hash {
+                        outrepo.set_hash(&name, hash);
+                    }
+                }
+            }
+        }
+
+        // Pushed mods whose checkSum differs from what players have, the others were filled in above
+        let mut changed: Vec<String> = Vec::new();
+        for (_, outrepo) in &outrepos {
+            for m in outrepo.mods().filter(|m| pushing.contains(&m.modName)) {
+                let before = previous.as_ref().and_then(|p| p.repo.mods().find(|o| o.modName == m.modName).map(|o| o.checkSum.clone()));
+                if before.as_ref() != Some(&m.checkSum) && !changed.contains(&m.modName) {
+                    changed.push(m.modName.clone());
+                }
+            }
+        }
+        let mut env = env.clone();
+        env.push(("SMOOTHLY_CHANGED".to_owned(), changed.join("\n")));
+        warn(hook(args, hooks::POST_SRF, &env));
+
+        // Only mods pushed this time were read, conflicts with the others would go unnoticed
+        if mods.is_empty() {
+            for (_, outrepo) in &outrepos {
+                let loaded = conflicts::candidates(outrepo);
+                let pbos: Vec<conflicts::Pbo> = pbos.iter().filter(|p| loaded.contains(&p.addon)).cloned().collect();
+                for conflict in conflicts::find(&pbos) {
+                    print!("{} {}: {}", "Warning:".yellow(), outrepo.repoName, conflict);
+                }
+                for unresolved in deps::unresolved(outrepo, &patches, &[]) {
+                    print!("{} {}: {}", "Warning:".yellow(), outrepo.repoName, unresolved);
+                }
             }
         }
 
         println!("Generating Repofile");
 
-        let j = serde_json::to_string_pretty(&outrepo).unwrap();
-        let mut fout = File::create(repofile).unwrap();
-        fout.write_all(j.as_bytes()).unwrap();
+        let format = args.value_of("format-version").unwrap();
+        let options = publish::Options {
+            base_path: args.value_of("published-base-path").unwrap_or("").to_owned(),
+            passwords: !args.is_present("omit-passwords"),
+        };
+        for (file, outrepo) in &outrepos {
+            let j = publish::render(outrepo, format, &options)?;
+            let mut fout = link::create(&PathBuf::from(format!("{}{}{}", dir, std::path::MAIN_SEPARATOR, file)))?;
+            fout.write_all(j.as_bytes()).unwrap();
+        }
+
+        warn(hook(args, hooks::POST_PUBLISH, &env));
+
+        // Figured from the published SRFs, so selective pushes are estimated correctly too
+        let published = srf::Published::load(&PathBuf::from(dir), "repo.json")?;
+        println!("Download size");
+        super::size::report(previous.as_ref(), &published, false);
+
+        let keep: usize = args.value_of("keep-snapshots").unwrap().parse()
+            .map_err(|_| SmoothlyError::GENERIC("--keep-snapshots must be a number".to_owned()))?;
+        if keep != 0 {
+            if link_mode == LinkMode::Hardlink {
+                println!("{} Snapshots share their files with basePath in hardlink mode, editing a mod file in place changes them too", "Warning:".yellow());
+            }
+            let dir = PathBuf::from(dir);
+            // The push itself succeeded, a snapshot that can't be made is only reported
+            match snapshot::create(&dir, outrepos.iter().map(|(_, r)| r.repoName.clone()).collect()) {
+                Ok(snapshot) => {
+                    println!("Created snapshot {}", snapshot.id);
+                    snapshot::prune(&dir, keep)?;
+                },
+                Err(e) => println!("{} {}, use --keep-snapshots 0 to disable them", "Warning:".yellow(), e),
+            }
+        }
+
+        if let (Some(url), false) = (args.value_of("webhook"), args.is_present("no-notify")) {
+            let retries: u32 = args.value_of("webhook-retries").unwrap().parse()
+                .map_err(|_| SmoothlyError::GENERIC("--webhook-retries must be a number".to_owned()))?;
+            // The push itself succeeded, a failed notification is only reported
+            match notify::send(url, &notify::payload(previous.as_ref(), &published), retries) {
+                Ok(_) => println!("Notified the webhook"),
+                Err(e) => println!("{}", e),
+            }
+        }
 
         Ok(())
     }
 }
 
-fn transform_u32_to_array_of_u8(x:u32) -> [u8;4] {
-    let b1 : u8 = ((x >> 24) & 0xff) as u8;
-    let b2 : u8 = ((x >> 16) & 0xff) as u8;
-    let b3 : u8 = ((x >> 8) & 0xff) as u8;
-    let b4 : u8 = (x & 0xff) as u8;
-    return [b1, b2, b3, b4]
+/// The repositories to push and the file names they are published as
+///
+/// The main repository is published as repo.json, the others keep their own file names
+/// unless given one after a `=`.
+fn repos(args: &clap::ArgMatches, repo_file: &str) -> Result<Vec<(String, Repo)>, SmoothlyError> {
+    let mut repos = vec![("repo.json".to_owned(), Repo::new(repo_file.to_owned())?)];
+    if let Some(others) = args.values_of("with") {
+        for other in others {
+            let (other, file) = match other.rfind('=') {
+                Some(i) => (&other[..i], other[i + 1..].to_owned()),
+                None => (other, PathBuf::from(other).file_name().unwrap().to_str().unwrap().to_owned()),
+            };
+            if file.is_empty() || PathBuf::from(&file).file_name().and_then(|f| f.to_str()) != Some(file.as_str()) {
+                return Err(SmoothlyError::GENERIC(format!("`{}` is not a file name to publish `{}` as", file, other)));
+            }
+            if repos.iter().any(|(f, _)| *f == file) {
+                return Err(SmoothlyError::GENERIC(format!("Another repository is already published as `{}`, choose another name with `--with {}=<name>.json`", file, other)));
+            }
+            let other = Repo::new(other.to_owned())?;
+            if PathBuf::from(&other.basePath) != PathBuf::from(&repos[0].1.basePath) {
+                return Err(SmoothlyError::GENERIC(format!("`{}` uses a different basePath ({})", other.repoName, other.basePath)));
+            }
+            repos.push((file, other));
+        }
+    }
+    Ok(repos)
+}
+
+fn hook(args: &clap::ArgMatches, name: &str, env: &[(String, String)]) -> Result<(), SmoothlyError> {
+    let commands: Vec<String> = args.values_of(name).map(|v| v.map(|s| s.to_owned()).collect()).unwrap_or_default();
+    hooks::run(name, &commands, env)
+}
+
+/// Only a failing pre-push hook stops the push, later ones are reported
+fn warn(result: Result<(), SmoothlyError>) {
+    if let Err(e) = result {
+        println!("{} {}", "Warning:".yellow(), e);
+    }
 }
@@ -0,0 +1,35 @@
+use crate::lock::Lock;
+use crate::project::Settings;
+use crate::{snapshot, Command, SmoothlyError};
+
+pub struct Rollback {}
+
+impl Command for Rollback {
+    fn register(&self) -> clap::App {
+        clap::SubCommand::with_name("rollback")
+            .about("Restore an output directory to a snapshot")
+            .args(&super::dir_args())
+            .arg(clap::Arg::with_name("id")
+                .help("Snapshot to restore, see `snapshots list`")
+            ).arg(clap::Arg::with_name("wait")
+                .help("Wait for another command using the output directory instead of failing")
+                .long("wait")
+            )
+    }
+
+    fn needs_repo(&self) -> bool {
+        false
+    }
+
+    fn run(&self, args: &clap::ArgMatches, _: String, settings: &Settings) -> Result<(), SmoothlyError> {
+        let (dir, positionals) = super::positionals(args, settings, &["id"], 1, false)?;
+        let _lock = Lock::acquire(&dir, args.is_present("wait"))?;
+        let target = snapshot::find(&dir, &positionals[0])?;
+        // Keep the state being replaced so the rollback can be undone
+        let current = snapshot::create(&dir, vec!["before rollback".to_owned()])?;
+        println!("Saved the current state as {}", current.id);
+        snapshot::restore(&dir, &target)?;
+        println!("Restored {} from {}", target.id, target.created());
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::project::Settings;
 use crate::{Command, SmoothlyError};
 
 pub struct SelfUpdate {}
@@ -8,7 +9,7 @@ impl Command for SelfUpdate {
             .about("Update Smoothly")
     }
 
-    fn run(&self, _: &clap::ArgMatches, _: String) -> Result<(), SmoothlyError> {
+    fn run(&self, _: &clap::ArgMatches, _: String, _: &Settings) -> Result<(), SmoothlyError> {
         let status = self_update::backends::github::Update::configure()
             .repo_owner("synixebrett")
             .repo_name("smoothly")
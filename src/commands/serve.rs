@@ -0,0 +1,344 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use colored::*;
+
+use crate::project::Settings;
+use crate::{snapshot, SmoothlyError, Command};
+
+pub struct Serve {}
+
+impl Command for Serve {
+    fn register(&self) -> clap::App {
+        clap::SubCommand::with_name("serve")
+            .about("Serve an output directory to Swifty clients")
+            .args(&super::dir_args())
+            .arg(clap::Arg::with_name("port")
+                .help("Port to listen on")
+                .long("port")
+                .takes_value(true)
+                .default_value("8080")
+            ).arg(clap::Arg::with_name("bind")
+                .help("Address to listen on")
+                .long("bind")
+                .takes_value(true)
+                .default_value("0.0.0.0")
+            ).arg(clap::Arg::with_name("throttle")
+                .help("Limit each connection to this many KiB/s")
+                .long("throttle")
+                .takes_value(true)
+            )
+    }
+
+    fn needs_repo(&self) -> bool {
+        false
+    }
+
+    fn run(&self, args: &clap::ArgMatches, _: String, settings: &Settings) -> Result<(), SmoothlyError> {
+        let (dir, _) = super::positionals(args, settings, &[], 0, false)?;
+        if !dir.is_dir() {
+            return Err(SmoothlyError::GENERIC(format!("`{}` is not a directory", dir.display())));
+        }
+        let port: u16 = args.value_of("port").unwrap().parse()
+            .map_err(|_| SmoothlyError::GENERIC("The port must be a number between 0 and 65535".to_owned()))?;
+        let throttle = match args.value_of("throttle") {
+            Some(t) => match t.parse::<u64>() {
+                Ok(kib) if kib > 0 => Some(kib * 1024),
+                _ => return Err(SmoothlyError::GENERIC("The throttle must be a positive number of KiB/s".to_owned())),
+            },
+            None => None,
+        };
+
+        let listener = TcpListener::bind((args.value_of("bind").unwrap(), port))?;
+        println!("Serving {} on http://{}", dir.display(), listener.local_addr()?);
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    println!("{}", format!("Connection failed: {}", e).red());
+                    continue;
+                }
+            };
+            let dir = dir.clone();
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                if let Err(e) = handle(stream, &dir, throttle, &peer) {
+                    println!("{} {}", peer, format!("{}", e).red());
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    range: Option<String>,
+    close: bool,
+}
+
+/// Serve requests on a connection until the client closes it
+fn handle(stream: TcpStream, dir: &Path, throttle: Option<u64>, peer: &str) -> Result<(), SmoothlyError> {
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = Throttled::new(stream, throttle);
+    loop {
+        let request = match read_request(&mut reader)? {
+            Some(r) => r,
+            None => return Ok(()),
+        };
+        let (status, sent) = respond(&mut writer, dir, &request)?;
+        println!("{} {} {} {} {} {}",
+            peer,
+            request.method,
+            request.path,
+            request.range.as_ref().map(|r| r.as_str()).unwrap_or("-"),
+            match status {
+                200 | 206 => status.to_string().green(),
+                _ => status.to_string().red(),
+            },
+            sent,
+        );
+        if request.close {
+            return Ok(());
+        }
+    }
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Result<Option<Request>, SmoothlyError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_owned();
+    let path = decode(parts.next().unwrap_or("/"));
+    let mut close = parts.next() != Some("HTTP/1.1");
+    let mut range = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let mut kv = header.splitn(2, ':');
+        let key = kv.next().unwrap_or("").trim().to_lowercase();
+        let value = kv.next().unwrap_or("").trim().to_owned();
+        match key.as_str() {
+            "range" => range = Some(value),
+            "connection" => close = value.eq_ignore_ascii_case("close"),
+            _ => {}
+        }
+    }
+    Ok(Some(Request { method, path, range, close }))
+}
+
+/// Write the response to `request`, returns the status and number of body bytes sent
+fn respond<W: Write>(out: &mut W, dir: &Path, request: &Request) -> Result<(u16, u64), SmoothlyError> {
+    if request.method != "GET" && request.method != "HEAD" {
+        return status(out, 405, "Method Not Allowed");
+    }
+    let path = match resolve(dir, &request.path) {
+        Some(p) => p,
+        None => return status(out, 404, "Not Found"),
+    };
+    let mut file = File::open(&path)?;
+    let length = file.metadata()?.len();
+    let head = request.method == "HEAD";
+    let content_type = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => "application/json",
+        Some("png") => "image/png",
+        _ => "application/octet-stream",
+    };
+
+    let ranges = match &request.range {
+        Some(r) => match parse_ranges(r, length) {
+            Some(r) => r,
+            None => {
+                write!(out, "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\nContent-Length: 0\r\n\r\n", length)?;
+                return Ok((416, 0));
+            }
+        },
+        None => Vec::new(),
+    };
+
+    match ranges.len() {
+        0 => {
+            write!(out, "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n\r\n", content_type, length)?;
+            if head {
+                return Ok((200, 0));
+            }
+            Ok((200, send(out, &mut file, 0, length)?))
+        },
+        1 => {
+            let (start, end) = ranges[0];
+            write!(out, "HTTP/1.1 206 Partial Content\r\nContent-Type: {}\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nAccept-Ranges: bytes\r\n\r\n",
+                content_type, end - start + 1, start, end, length)?;
+            if head {
+                return Ok((206, 0));
+            }
+            Ok((206, send(out, &mut file, start, end - start + 1)?))
+        },
+        _ => {
+            let boundary = "SMOOTHLY_BYTERANGES";
+            let headers: Vec<String> = ranges.iter().map(|(start, end)| {
+                format!("\r\n--{}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n", boundary, content_type, start, end, length)
+            }).collect();
+            let closing = format!("\r\n--{}--\r\n", boundary);
+            let total = headers.iter().map(|h| h.len() as u64).sum::<u64>()
+                + ranges.iter().map(|(start, end)| end - start + 1).sum::<u64>()
+                + closing.len() as u64;
+            write!(out, "HTTP/1.1 206 Partial Content\r\nContent-Type: multipart/byteranges; boundary={}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n\r\n", boundary, total)?;
+            if head {
+                return Ok((206, 0));
+            }
+            let mut sent = 0;
+            for (header, (start, end)) in headers.iter().zip(ranges.iter()) {
+                out.write_all(header.as_bytes())?;
+                sent += send(out, &mut file, *start, end - start + 1)?;
+            }
+            out.write_all(closing.as_bytes())?;
+            Ok((206, sent))
+        },
+    }
+}
+
+fn status<W: Write>(out: &mut W, code: u16, reason: &str) -> Result<(u16, u64), SmoothlyError> {
+    write!(out, "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n\r\n{}", code, reason, reason.len(), reason)?;
+    Ok((code, 0))
+}
+
+fn send<W: Write>(out: &mut W, file: &mut File, start: u64, length: u64) -> Result<u64, SmoothlyError> {
+    file.seek(SeekFrom::Start(start))?;
+    Ok(std::io::copy(&mut file.take(length), out)?)
+}
+
+/// Map a request path to a file inside `dir`, refusing anything that escapes it
+fn resolve(dir: &Path, path: &str) -> Option<PathBuf> {
+    let path = path.split('?').next().unwrap_or("");
+    let mut out = dir.to_path_buf();
+    for component in Path::new(path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(c) if c == snapshot::STATE_DIR => return None,
+            Component::Normal(c) => out.push(c),
+            Component::CurDir => {},
+            _ => return None,
+        }
+    }
+    if out.is_file() { Some(out) } else { None }
+}
+
+/// Parse a `bytes=` range header into inclusive ranges, `None` if nothing is satisfiable
+fn parse_ranges(header: &str, length: u64) -> Option<Vec<(u64, u64)>> {
+    if !header.starts_with("bytes=") {
+        return None;
+    }
+    let mut ranges = Vec::new();
+    for spec in header[6..].split(',') {
+        let mut bounds = spec.trim().splitn(2, '-');
+        let start = bounds.next()?.trim();
+        let end = bounds.next()?.trim();
+        let (start, end) = if start.is_empty() {
+            // Suffix range, the last `end` bytes
+            let suffix: u64 = end.parse().ok()?;
+            (length.saturating_sub(suffix), length.checked_sub(1)?)
+        } else {
+            let start: u64 = start.parse().ok()?;
+            let end = if end.is_empty() { length.checked_sub(1)? } else { std::cmp::min(end.parse().ok()?, length.checked_sub(1)?) };
+            (start, end)
+        };
+        if start <= end && start < length {
+            ranges.push((start, end));
+        }
+    }
+    if ranges.is_empty() { None } else { Some(ranges) }
+}
+
+/// Decode `%XX` escapes in a request path
+fn decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok().and_then(|h| u8::from_str_radix(h, 16).ok());
+            if let Some(b) = hex {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// A writer that limits how fast data is sent, to simulate slow clients
+struct Throttled<W: Write> {
+    inner: W,
+    limit: Option<u64>,
+    started: Instant,
+    written: u64,
+}
+
+impl<W: Write> Throttled<W> {
+    fn new(inner: W, limit: Option<u64>) -> Self {
+        Self {
+            inner,
+            limit,
+            started: Instant::now(),
+            written: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for Throttled<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let limit = match self.limit {
+            Some(l) => l,
+            None => return self.inner.write(buf),
+        };
+        // Small chunks keep the rate smooth
+        let chunk = std::cmp::min(buf.len(), std::cmp::max(1, limit as usize / 10));
+        let written = self.inner.write(&buf[..chunk])?;
+        self.written += written as u64;
+        let expected = Duration::from_millis(self.written * 1000 / limit);
+        let elapsed = self.started.elapsed();
+        if expected > elapsed {
+            std::thread::sleep(expected - elapsed);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_ranges("bytes=0-9", 100), Some(vec![(0, 9)]));
+        assert_eq!(parse_ranges("bytes=90-", 100), Some(vec![(90, 99)]));
+        assert_eq!(parse_ranges("bytes=-10", 100), Some(vec![(90, 99)]));
+        assert_eq!(parse_ranges("bytes=50-200", 100), Some(vec![(50, 99)]));
+        assert_eq!(parse_ranges("bytes=0-0, 10-19", 100), Some(vec![(0, 0), (10, 19)]));
+    }
+
+    #[test]
+    fn unsatisfiable_ranges() {
+        assert_eq!(parse_ranges("bytes=100-", 100), None);
+        assert_eq!(parse_ranges("bytes=9-0", 100), None);
+        assert_eq!(parse_ranges("bytes=0-9", 0), None);
+        assert_eq!(parse_ranges("items=0-9", 100), None);
+        assert_eq!(parse_ranges("bytes=a-9", 100), None);
+    }
+}
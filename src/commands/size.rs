@@ -0,0 +1,61 @@
+use crate::project::Settings;
+use crate::srf::{self, Published};
+use crate::{Command, SmoothlyError};
+
+pub struct Size {}
+
+impl Command for Size {
+    fn register(&self) -> clap::App {
+        clap::SubCommand::with_name("size")
+            .about("Estimate what players download for a published state")
+            .args(&super::dir_args())
+            .arg(clap::Arg::with_name("from")
+                .help("Snapshot id or directory of the version players have, only fresh installs are estimated without it")
+            ).arg(clap::Arg::with_name("to")
+                .help("Snapshot id or directory of the new version, defaults to the output directory")
+            ).arg(clap::Arg::with_name("file")
+                .help("Repository file to compare")
+                .long("file")
+                .takes_value(true)
+                .default_value("repo.json")
+            )
+    }
+
+    fn needs_repo(&self) -> bool {
+        false
+    }
+
+    fn run(&self, args: &clap::ArgMatches, _: String, settings: &Settings) -> Result<(), SmoothlyError> {
+        let (dir, positionals) = super::positionals(args, settings, &["from", "to"], 0, false)?;
+        let file = args.value_of("file").unwrap();
+        let old = match positionals.get(0) {
+            Some(from) => Some(Published::locate(&dir, from, file)?),
+            None => None,
+        };
+        let new = match positionals.get(1) {
+            Some(to) => Published::locate(&dir, to, file)?,
+            None => Published::load(&dir, file)?,
+        };
+        report(old.as_ref(), &new, true);
+        Ok(())
+    }
+}
+
+/// Print the download estimate, `all` includes the mods without changes
+pub fn report(old: Option<&Published>, new: &Published, all: bool) {
+    let estimates = srf::estimate(old, new);
+    let width = estimates.iter().map(|e| e.addon.len()).max().unwrap_or(0);
+    for estimate in &estimates {
+        if !all && estimate.update == 0 { continue; }
+        match old {
+            Some(_) => println!(" - {:w$}  {:>12} update  {:>12} fresh", estimate.addon, srf::human(estimate.update), srf::human(estimate.fresh), w = width),
+            None => println!(" - {:w$}  {:>12}", estimate.addon, srf::human(estimate.fresh), w = width),
+        }
+    }
+    let update: u64 = estimates.iter().map(|e| e.update).sum();
+    let fresh: u64 = estimates.iter().map(|e| e.fresh).sum();
+    if old.is_some() {
+        println!("Players on the previous version download {}", srf::human(update));
+    }
+    println!("A fresh install downloads {}", srf::human(fresh));
+}
@@ -0,0 +1,49 @@
+use crate::lock::Lock;
+use crate::project::Settings;
+use crate::{snapshot, Command, SmoothlyError};
+
+pub struct Snapshots {}
+
+impl Command for Snapshots {
+    fn register(&self) -> clap::App {
+        clap::SubCommand::with_name("snapshots")
+            .about("Manage the published snapshots of an output directory")
+            .subcommand(clap::SubCommand::with_name("list")
+                .about("List the snapshots")
+                .args(&super::dir_args())
+            ).subcommand(clap::SubCommand::with_name("create")
+                .about("Snapshot the current contents")
+                .args(&super::dir_args()).arg(clap::Arg::with_name("wait")
+                    .help("Wait for another command using the output directory instead of failing")
+                    .long("wait")
+                )
+            )
+    }
+
+    fn needs_repo(&self) -> bool {
+        false
+    }
+
+    fn run(&self, args: &clap::ArgMatches, _: String, settings: &Settings) -> Result<(), SmoothlyError> {
+        match args.subcommand() {
+            ("list", Some(sub)) => {
+                let (dir, _) = super::positionals(sub, settings, &[], 0, false)?;
+                let snapshots = snapshot::list(&dir)?;
+                if snapshots.is_empty() {
+                    println!("No snapshots in {}", dir.display());
+                }
+                for s in snapshots {
+                    println!("{}  {}  {}", s.id, s.created(), s.repos.join(", "));
+                }
+            },
+            ("create", Some(sub)) => {
+                let (dir, _) = super::positionals(sub, settings, &[], 0, false)?;
+                let _lock = Lock::acquire(&dir, sub.is_present("wait"))?;
+                let s = snapshot::create(&dir, Vec::new())?;
+                println!("Created snapshot {}", s.id);
+            },
+            _ => println!("No command"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+//! Reading Arma configs, rapified config.bin or plain config.cpp
+
+use crate::SmoothlyError;
+
+/// A config class, only what Smoothly needs from it
+#[derive(Debug, Clone, Default)]
+pub struct Class {
+    pub name: String,
+    pub classes: Vec<Class>,
+    pub arrays: Vec<(String, Vec<String>)>,
+}
+
+impl Class {
+    pub fn class(&self, name: &str) -> Option<&Class> {
+        self.classes.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn array(&self, name: &str) -> Option<&Vec<String>> {
+        self.arrays.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, a)| a)
+    }
+}
+
+/// Parse a config from a PBO entry, picking the format from its name
+pub fn parse(name: &str, data: &[u8]) -> Result<Class, SmoothlyError> {
+    if name.to_lowercase().ends_with(".bin") || data.starts_with(b"\0raP") {
+        Rap::new(data).root()
+    } else {
+        Text::new(&String::from_utf8_lossy(data)).root()
+    }
+}
+
+struct Rap<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Rap<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn error(&self) -> SmoothlyError {
+        SmoothlyError::GENERIC("Invalid rapified config".to_owned())
+    }
+
+    fn root(&self) -> Result<Class, SmoothlyError> {
+        if !self.data.starts_with(b"\0raP") {
+            return Err(self.error());
+        }
+        // Signature, two reserved u32 and the enum offset
+        self.class(String::new(), 16)
+    }
+
+    fn byte(&self, pos: &mut usize) -> Result<u8, SmoothlyError> {
+        let b = *self.data.get(*pos).ok_or_else(|| self.error())?;
+        *pos += 1;
+        Ok(b)
+    }
+
+    fn u32(&self, pos: &mut usize) -> Result<u32, SmoothlyError> {
+        let mut v = 0;
+        for i in 0..4 {
+            v |= u32::from(self.byte(pos)?) << (8 * i);
+        }
+        Ok(v)
+    }
+
+    fn compressed(&self, pos: &mut usize) -> Result<u32, SmoothlyError> {
+        let mut v = 0;
+        for i in 0..5 {
+            let b = self.byte(pos)?;
+            v |= u32::from(b & 0x7f) << (7 * i);
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(self.error())
+    }
+
+    fn string(&self, pos: &mut usize) -> Result<String, SmoothlyError> {
+        let rest = self.data.get(*pos..).ok_or_else(|| self.error())?;
+        let end = rest.iter().position(|b| *b == 0).ok_or_else(|| self.error())?;
+        let s = String::from_utf8_lossy(&self.data[*pos..*pos + end]).into_owned();
+        *pos += end + 1;
+        Ok(s)
+    }
+
+    fn class(&self, name: String, offset: usize) -> Result<Class, SmoothlyError> {
+        let mut pos = offset;
+        let mut class = Class { name, ..Class::default() };
+        let _parent = self.string(&mut pos)?;
+        let entries = self.compressed(&mut pos)?;
+        for _ in 0..entries {
+            match self.byte(&mut pos)? {
+                0 => {
+                    let name = self.string(&mut pos)?;
+                    let body = self.u32(&mut pos)? as usize;
+                    class.classes.push(self.class(name, body)?);
+                },
+                1 => {
+                    let kind = self.byte(&mut pos)?;
+                    self.string(&mut pos)?;
+                    match kind {
+                        0 | 4 => { self.string(&mut pos)?; },
+                        6 => pos += 8,
+                        _ => pos += 4,
+                    }
+                },
+                2 => {
+                    let name = self.string(&mut pos)?;
+                    class.arrays.push((name, self.array(&mut pos)?));
+                },
+                3 | 4 => { self.string(&mut pos)?; },
+                5 => {
+                    pos += 4;
+                    let name = self.string(&mut pos)?;
+                    class.arrays.push((name, self.array(&mut pos)?));
+                },
+                _ => return Err(self.error()),
+            }
+        }
+        Ok(class)
+    }
+
+    /// Flatten an array to its strings, nested arrays included
+    fn array(&self, pos: &mut usize) -> Result<Vec<String>, SmoothlyError> {
+        let mut out = Vec::new();
+        let count = self.compressed(pos)?;
+        for _ in 0..count {
+            match self.byte(pos)? {
+                0 | 4 => out.push(self.string(pos)?),
+                3 => out.append(&mut self.array(pos)?),
+                6 => *pos += 8,
+                _ => *pos += 4,
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Symbol(char),
+}
+
+struct Text {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Text {
+    fn new(source: &str) -> Self {
+        Self { tokens: tokenize(source), pos: 0 }
+    }
+
+    fn root(&mut self) -> Result<Class, SmoothlyError> {
+        let mut root = Class::default();
+        self.body(&mut root, false)?;
+        Ok(root)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn error(&self) -> SmoothlyError {
+        SmoothlyError::GENERIC(format!("Unable to parse config.cpp near token {}", self.pos))
+    }
+
+    /// Read statements until the closing brace of the class, or the end of the file
+    fn body(&mut self, class: &mut Class, nested: bool) -> Result<(), SmoothlyError> {
+        loop {
+            let token = match self.next() {
+                Some(t) => t,
+                None if nested => return Err(self.error()),
+                None => return Ok(()),
+            };
+            match token {
+                Token::Symbol('}') if nested => {
+                    if self.peek() == Some(&Token::Symbol(';')) { self.pos += 1; }
+                    return Ok(());
+                },
+                Token::Symbol(';') => {},
+                Token::Word(ref w) if w == "class" => {
+                    let name = match self.next() {
+                        Some(Token::Word(n)) => n,
+                        _ => return Err(self.error()),
+                    };
+                    if self.peek() == Some(&Token::Symbol(':')) {
+                        self.pos += 2;
+                    }
+                    match self.next() {
+                        Some(Token::Symbol('{')) => {
+                            let mut child = Class { name, ..Class::default() };
+                            self.body(&mut child, true)?;
+                            class.classes.push(child);
+                        },
+                        Some(Token::Symbol(';')) => {},
+                        _ => return Err(self.error()),
+                    }
+                },
+                Token::Word(ref w) if w == "delete" => {
+                    self.next();
+                },
+                Token::Word(name) => {
+                    if self.peek() == Some(&Token::Symbol('[')) {
+                        // name[] = {...} or name[] += {...}
+                        while let Some(t) = self.next() {
+                            if t == Token::Symbol('{') { break; }
+                        }
+                        let values = self.array()?;
+                        class.arrays.push((name, values));
+                    } else {
+                        // Plain values are skipped up to the end of the statement
+                        while let Some(t) = self.next() {
+                            if t == Token::Symbol(';') { break; }
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Strings and words of an array up to its closing brace
+    fn array(&mut self) -> Result<Vec<String>, SmoothlyError> {
+        let mut out = Vec::new();
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next() {
+                Some(Token::Symbol('{')) => depth += 1,
+                Some(Token::Symbol('}')) => depth -= 1,
+                Some(Token::Str(s)) | Some(Token::Word(s)) => out.push(s),
+                Some(_) => {},
+                None => return Err(self.error()),
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let chars: Vec<char> = source.chars().collect();
+    let mut i = 0;
+    let mut line_start = true;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line_start = true;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        // Preprocessor directives, including continued lines
+        if line_start && c == '#' {
+            while i < chars.len() && (chars[i] != '\n' || chars[i - 1] == '\\') {
+                i += 1;
+            }
+            continue;
+        }
+        line_start = false;
+        if c == '/' && chars.get(i + 1) == Some(&'/') {
+            while i < chars.len() && chars[i] != '\n' { i += 1; }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i + 1 < chars.len() && !(chars[i] == '*' && chars[i + 1] == '/') { i += 1; }
+            i += 2;
+            continue;
+        }
+        if c == '"' {
+            // Quotes inside strings are doubled
+            let mut s = String::new();
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == '"' {
+                    if chars.get(i + 1) == Some(&'"') {
+                        s.push('"');
+                        i += 2;
+                        continue;
+                    }
+                    break;
+                }
+                s.push(chars[i]);
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Str(s));
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' {
+            let mut s = String::new();
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.' || chars[i] == '-') {
+                s.push(chars[i]);
+                i += 1;
+            }
+            tokens.push(Token::Word(s));
+            continue;
+        }
+        tokens.push(Token::Symbol(c));
+        i += 1;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text() {
+        let root = parse("config.cpp", br#"
+            #include "script_component.hpp"
+            class CfgPatches {
+                class X: Y {
+                    units[] = {};
+                    requiredVersion = 1.0;
+                    requiredAddons[] = {"a", "b"}; // trailing comment
+                };
+                class Z {
+                    requiredAddons[] += {"c"};
+                };
+            };
+        "#).unwrap();
+        let patches = root.class("cfgpatches").unwrap();
+        assert_eq!(patches.classes.len(), 2);
+        assert_eq!(patches.class("X").unwrap().array("requiredAddons"), Some(&vec!["a".to_owned(), "b".to_owned()]));
+        assert_eq!(patches.class("Z").unwrap().array("requiredAddons"), Some(&vec!["c".to_owned()]));
+    }
+
+    #[test]
+    fn text_unclosed() {
+        assert!(parse("config.cpp", b"class CfgPatches { class X {").is_err());
+    }
+
+    #[test]
+    fn rapified() {
+        fn u32(data: &mut Vec<u8>, v: usize) {
+            data.extend(&(v as u32).to_le_bytes());
+        }
+        let mut data = b"\0raP".to_vec();
+        u32(&mut data, 0);
+        u32(&mut data, 8);
+        u32(&mut data, 0);
+        // Root: no parent, one class entry
+        data.extend(b"\0\x01\x00CfgPatches\0");
+        let cfgpatches = data.len() + 4;
+        u32(&mut data, cfgpatches);
+        data.extend(b"\0\x01\x00X\0");
+        let x = data.len() + 4;
+        u32(&mut data, x);
+        // X: Y with one array of two strings
+        data.extend(b"Y\0\x01\x02requiredAddons\0\x02\x00a\0\x00b\0");
+
+        let root = parse("config.bin", &data).unwrap();
+        let x = root.class("CfgPatches").unwrap().class("X").unwrap();
+        assert_eq!(x.array("requiredAddons"), Some(&vec!["a".to_owned(), "b".to_owned()]));
+    }
+
+    #[test]
+    fn rapified_truncated() {
+        assert!(parse("config.bin", b"\0raP\0\0\0\0\x08\0\0\0\0\0\0\0\0\x01\x00X\0").is_err());
+    }
+}
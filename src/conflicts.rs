@@ -0,0 +1,114 @@
+use std::fs::File;
+use std::path::Path;
+
+use hashbrown::HashMap;
+use pbo::PBO;
+
+use crate::{Repo, SmoothlyError};
+
+/// A PBO that Arma loads, in the `addons` folder of a mod
+#[derive(Debug, Clone)]
+pub struct Pbo {
+    pub addon: String,
+    pub file: String,
+    pub prefix: Option<String>,
+}
+
+impl Pbo {
+    /// Describe `pbo`, `None` if Arma doesn't load it
+    pub fn new(addon: &str, file: &str, pbo: &PBO) -> Option<Self> {
+        let mut parts = file.split('\\');
+        let folder = parts.next()?;
+        if !folder.eq_ignore_ascii_case("addons") || parts.clone().count() != 1 {
+            return None;
+        }
+        Some(Self {
+            addon: addon.to_owned(),
+            file: parts.next()?.to_owned(),
+            prefix: pbo.extensions.get("prefix").map(|p| p.trim_matches('\\').to_owned()),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Kind {
+    Name,
+    Prefix,
+}
+
+/// PBOs from different mods sharing a file name or prefix, the later one wins in game
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub kind: Kind,
+    pub key: String,
+    pub pbos: Vec<Pbo>,
+}
+
+/// Read the PBOs of a mod folder
+pub fn scan(moddir: &Path, addon: &str) -> Result<Vec<Pbo>, SmoothlyError> {
+    let mut pbos = Vec::new();
+    let addons = match std::fs::read_dir(moddir)?
+        .filter_map(|e| e.ok())
+        .find(|e| e.file_name().to_string_lossy().eq_ignore_ascii_case("addons")) {
+        Some(e) => e.path(),
+        None => return Ok(pbos),
+    };
+    for entry in std::fs::read_dir(addons)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if !name.to_lowercase().ends_with(".pbo") { continue; }
+        let pbo = PBO::read(&mut File::open(&path)?)
+            .map_err(|e| SmoothlyError::GENERIC(format!("Unable to read {}: {}", path.display(), e)))?;
+        if let Some(p) = Pbo::new(addon, &format!("addons\\{}", name), &pbo) {
+            pbos.push(p);
+        }
+    }
+    Ok(pbos)
+}
+
+/// Mods whose PBOs can be loaded together, enabled required mods and every optional mod
+pub fn candidates(repo: &Repo) -> Vec<String> {
+    repo.requiredMods.iter().filter(|m| m.Enabled)
+        .chain(repo.optionalMods.iter())
+        .map(|m| m.modName.clone())
+        .collect()
+}
+
+pub fn find(pbos: &[Pbo]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    for kind in &[Kind::Name, Kind::Prefix] {
+        let mut groups: HashMap<String, Vec<Pbo>> = HashMap::new();
+        for pbo in pbos {
+            let key = match kind {
+                Kind::Name => pbo.file.to_lowercase(),
+                Kind::Prefix => match &pbo.prefix {
+                    Some(p) if !p.is_empty() => p.to_lowercase(),
+                    _ => continue,
+                },
+            };
+            groups.entry(key).or_insert_with(Vec::new).push(pbo.clone());
+        }
+        for (key, group) in groups {
+            let first = &group[0].addon;
+            if group.iter().any(|p| p.addon != *first) {
+                conflicts.push(Conflict { kind: *kind, key, pbos: group });
+            }
+        }
+    }
+    conflicts.sort_by(|a, b| (a.kind as u8, &a.key).cmp(&(b.kind as u8, &b.key)));
+    conflicts
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let what = match self.kind {
+            Kind::Name => "PBO name",
+            Kind::Prefix => "prefix",
+        };
+        writeln!(f, "Duplicate {} `{}`", what, self.key)?;
+        for pbo in &self.pbos {
+            writeln!(f, " - {}\\addons\\{}", pbo.addon, pbo.file)?;
+        }
+        Ok(())
+    }
+}
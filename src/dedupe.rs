@@ -0,0 +1,68 @@
+use hashbrown::HashMap;
+
+use crate::srf::Published;
+
+/// A file in a published mod
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub addon: String,
+    pub file: String,
+}
+
+/// Identical files found in more than one mod
+#[derive(Debug, Clone)]
+pub struct Group {
+    pub hash: String,
+    pub size: u64,
+    pub copies: Vec<Location>,
+}
+
+impl Group {
+    /// Mods holding a copy, copies within one mod count once
+    pub fn mods(&self) -> Vec<&str> {
+        let mut mods: Vec<&str> = self.copies.iter().map(|c| c.addon.as_str()).collect();
+        mods.sort();
+        mods.dedup();
+        mods
+    }
+
+    /// Bytes stored beyond the first mod's copy
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.mods().len() as u64 - 1)
+    }
+
+    /// Bytes downloaded beyond the first copy by a player who only has the required mods
+    pub fn wasted_download(&self, published: &Published) -> u64 {
+        let required = self.mods().into_iter()
+            .filter(|addon| published.repo.requiredMods.iter().any(|m| m.modName == *addon))
+            .count() as u64;
+        self.size * required.saturating_sub(1)
+    }
+}
+
+/// Group the files of every published mod by hash and size, largest waste first
+pub fn find(published: &Published, min_size: u64) -> Vec<Group> {
+    let mut groups: HashMap<(String, u64), Vec<Location>> = HashMap::new();
+    for (name, addon) in &published.addons {
+        for file in &addon.files {
+            let size = file.size() as u64;
+            let hash = match &file.hash {
+                Some(h) if size >= min_size && size != 0 => h.clone(),
+                _ => continue,
+            };
+            groups.entry((hash, size)).or_insert_with(Vec::new).push(Location {
+                addon: name.clone(),
+                file: file.name.clone(),
+            });
+        }
+    }
+    let mut found: Vec<Group> = groups.into_iter()
+        .filter(|(_, copies)| copies.iter().any(|c| c.addon != copies[0].addon))
+        .map(|((hash, size), mut copies)| {
+            copies.sort_by(|a, b| (&a.addon, &a.file).cmp(&(&b.addon, &b.file)));
+            Group { hash, size, copies }
+        })
+        .collect();
+    found.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.hash.cmp(&b.hash)));
+    found
+}
@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::path::Path;
+
+use hashbrown::HashMap;
+use pbo::PBO;
+
+use crate::{config, Repo, SmoothlyError};
+
+/// A CfgPatches class and the addons it needs loaded first
+#[derive(Debug, Clone)]
+pub struct Patch {
+    pub addon: String,
+    pub name: String,
+    pub requires: Vec<String>,
+}
+
+/// A requiredAddons entry that no loaded mod provides
+#[derive(Debug, Clone)]
+pub struct Unresolved {
+    pub addon: String,
+    pub patch: String,
+    pub requires: String,
+    /// A mod outside of the configuration that does provide it
+    pub provider: Option<String>,
+}
+
+/// Read the CfgPatches of every config in `pbo`
+///
+/// Configs that can't be parsed are skipped, they will fail in game as well.
+pub fn patches(addon: &str, pbo: &PBO) -> Vec<Patch> {
+    let mut out = Vec::new();
+    for (name, file) in pbo.files.iter() {
+        let base = name.rsplit('\\').next().unwrap_or(name).to_lowercase();
+        if base != "config.bin" && base != "config.cpp" { continue; }
+        let root = match config::parse(name, &file.get_ref()[..]) {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+        if let Some(cfgpatches) = root.class("CfgPatches") {
+            for class in &cfgpatches.classes {
+                out.push(Patch {
+                    addon: addon.to_owned(),
+                    name: class.name.clone(),
+                    requires: class.array("requiredAddons").cloned().unwrap_or_default(),
+                });
+            }
+        }
+    }
+    out
+}
+
+/// Read the CfgPatches of every PBO in a mod folder
+pub fn scan(moddir: &Path, addon: &str) -> Result<Vec<Patch>, SmoothlyError> {
+    let mut out = Vec::new();
+    for entry in walkdir::WalkDir::new(moddir) {
+        let entry = entry.map_err(|e| SmoothlyError::GENERIC(e.to_string()))?;
+        let path = entry.path();
+        if !path.is_file() || !path.to_string_lossy().to_lowercase().ends_with(".pbo") { continue; }
+        let pbo = PBO::read(&mut File::open(&path)?)
+            .map_err(|e| SmoothlyError::GENERIC(format!("Unable to read {}: {}", path.display(), e)))?;
+        out.append(&mut patches(addon, &pbo));
+    }
+    Ok(out)
+}
+
+/// Patches that come with the game rather than a mod
+pub fn is_base_game(patch: &str, ignore: &[String]) -> bool {
+    let patch = patch.to_lowercase();
+    patch.starts_with("a3_") || ignore.iter().any(|p| patch.starts_with(&p.to_lowercase()))
+}
+
+/// Which mod provides each patch, by lowercase patch name
+fn providers(patches: &[Patch]) -> HashMap<String, String> {
+    let mut map = HashMap::new();
+    for patch in patches {
+        map.entry(patch.name.to_lowercase()).or_insert_with(|| patch.addon.clone());
+    }
+    map
+}
+
+/// Dependencies that are missing when the enabled required mods are loaded,
+/// alone and together with each optional mod
+pub fn unresolved(repo: &Repo, patches: &[Patch], ignore: &[String]) -> Vec<Unresolved> {
+    let providers = providers(patches);
+    let required: Vec<&String> = repo.requiredMods.iter().filter(|m| m.Enabled).map(|m| &m.modName).collect();
+    let mut out = Vec::new();
+    let mut check = |patch: &Patch, loaded: &dyn Fn(&String) -> bool| {
+        for requires in &patch.requires {
+            if is_base_game(requires, ignore) { continue; }
+            let provider = providers.get(&requires.to_lowercase());
+            if provider.map(|p| loaded(p)).unwrap_or(false) { continue; }
+            out.push(Unresolved {
+                addon: patch.addon.clone(),
+                patch: patch.name.clone(),
+                requires: requires.clone(),
+                provider: provider.cloned(),
+            });
+        }
+    };
+    for patch in patches {
+        if required.contains(&&patch.addon) {
+            check(patch, &|p| required.contains(&p));
+        } else if repo.optionalMods.iter().any(|m| m.modName == patch.addon) {
+            check(patch, &|p| *p == patch.addon || required.contains(&p));
+        }
+    }
+    out
+}
+
+/// Order `mods` so every mod comes after the mods it depends on, keeping the
+/// given order where there is a choice. Also returns the mods in a cycle.
+pub fn load_order(mods: &[String], patches: &[Patch]) -> (Vec<String>, Vec<String>) {
+    let providers = providers(patches);
+    let mut needs: HashMap<&String, Vec<String>> = HashMap::new();
+    for patch in patches {
+        for requires in &patch.requires {
+            if let Some(provider) = providers.get(&requires.to_lowercase()) {
+                if *provider != patch.addon && mods.contains(provider) {
+                    needs.entry(&patch.addon).or_insert_with(Vec::new).push(provider.clone());
+                }
+            }
+        }
+    }
+    let mut order: Vec<String> = Vec::new();
+    let mut cycles = Vec::new();
+    let mut remaining: Vec<&String> = mods.iter().collect();
+    while !remaining.is_empty() {
+        let ready = remaining.iter().position(|m| {
+            needs.get(m).map(|n| n.iter().all(|d| order.contains(d))).unwrap_or(true)
+        });
+        let next = match ready {
+            Some(i) => remaining.remove(i),
+            None => {
+                // Break the cycle at the first remaining mod that is part of one,
+                // the mods only depending on a cycle wait for it
+                let i = remaining.iter().position(|m| on_cycle(m, &needs, &order)).unwrap_or(0);
+                let m = remaining.remove(i);
+                cycles.push(m.clone());
+                m
+            },
+        };
+        order.push(next.clone());
+    }
+    (order, cycles)
+}
+
+/// Whether `m` can be reached again through the dependencies not in `order` yet
+fn on_cycle(m: &String, needs: &HashMap<&String, Vec<String>>, order: &[String]) -> bool {
+    let mut stack: Vec<&String> = needs.get(m).map(|n| n.iter().collect()).unwrap_or_default();
+    let mut seen: Vec<&String> = Vec::new();
+    while let Some(d) = stack.pop() {
+        if d == m {
+            return true;
+        }
+        if seen.contains(&d) || order.contains(d) { continue; }
+        seen.push(d);
+        if let Some(n) = needs.get(d) {
+            stack.extend(n.iter());
+        }
+    }
+    false
+}
+
+impl std::fmt::Display for Unresolved {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} ({}) requires `{}`", self.addon, self.patch, self.requires)?;
+        match &self.provider {
+            Some(p) => writeln!(f, ", provided by {} which is not loaded with it", p),
+            None => writeln!(f, ", which no mod provides"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(addon: &str, name: &str, requires: &[&str]) -> Patch {
+        Patch {
+            addon: addon.to_owned(),
+            name: name.to_owned(),
+            requires: requires.iter().map(|r| (*r).to_owned()).collect(),
+        }
+    }
+
+    fn mods(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| (*n).to_owned()).collect()
+    }
+
+    #[test]
+    fn order() {
+        let patches = vec![
+            patch("@ace", "ace_main", &["cba_main", "a3_data_f"]),
+            patch("@cba", "cba_main", &[]),
+        ];
+        let (order, cycles) = load_order(&mods(&["@ace", "@cba"]), &patches);
+        assert_eq!(order, mods(&["@cba", "@ace"]));
+        assert!(cycles.is_empty());
+    }
+
+    #[test]
+    fn cycle() {
+        let patches = vec![
+            patch("@c", "c_main", &["A_MAIN"]),
+            patch("@a", "a_main", &["b_main"]),
+            patch("@b", "b_main", &["a_main"]),
+        ];
+        let (order, cycles) = load_order(&mods(&["@c", "@a", "@b"]), &patches);
+        assert_eq!(order, mods(&["@a", "@c", "@b"]));
+        assert_eq!(cycles, mods(&["@a"]));
+    }
+}
@@ -0,0 +1,51 @@
+//! External commands run around a push
+
+use std::process::Command;
+
+use crate::SmoothlyError;
+
+/// Before anything is copied, a failure aborts the push
+pub const PRE_PUSH: &str = "pre-push";
+/// After each mod is copied to the output directory
+pub const POST_COPY: &str = "post-copy";
+/// After the mod.srf files are generated
+pub const POST_SRF: &str = "post-srf";
+/// After the repository files are published
+pub const POST_PUBLISH: &str = "post-publish";
+
+pub const ALL: &[&str] = &[PRE_PUSH, POST_COPY, POST_SRF, POST_PUBLISH];
+
+/// Run the commands of a hook in order through the shell, stopping at the first failure
+pub fn run(hook: &str, commands: &[String], env: &[(String, String)]) -> Result<(), SmoothlyError> {
+    for command in commands {
+        println!("Running {} hook `{}`", hook, command);
+        let mut process = shell(command);
+        process.env("SMOOTHLY_HOOK", hook);
+        for (key, value) in env {
+            process.env(key, value);
+        }
+        let status = process.status()
+            .map_err(|e| SmoothlyError::GENERIC(format!("Unable to run {} hook `{}`: {}", hook, command, e)))?;
+        if !status.success() {
+            return Err(SmoothlyError::GENERIC(match status.code() {
+                Some(code) => format!("{} hook `{}` failed with exit code {}", hook, command, code),
+                None => format!("{} hook `{}` was terminated", hook, command),
+            }));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut process = Command::new("cmd");
+    process.args(&["/C", command]);
+    process
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut process = Command::new("sh");
+    process.args(&["-c", command]);
+    process
+}
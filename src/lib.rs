@@ -6,6 +6,26 @@ pub mod macros;
 pub mod error;
 pub use error::{IOPathError, SmoothlyError};
 
+pub mod link;
+pub use link::LinkMode;
+
+pub mod config;
+pub mod conflicts;
+pub mod dedupe;
+pub mod deps;
+pub mod hooks;
+pub mod lint;
+pub mod lock;
+pub mod notify;
+pub mod png;
+pub mod progress;
+pub mod project;
+pub mod publish;
+pub mod snapshot;
+pub mod srf;
+pub mod version;
+pub mod watch;
+
 pub mod commands;
 pub use commands::Command;
 
@@ -19,6 +39,7 @@ pub enum Transaction {
     Remove,
     Ignore,
     Existing,
+    Missing,
 }
 
 #[derive(PartialEq, Debug, Clone)]
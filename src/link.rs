@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::progress::Progress;
+use crate::{IOPathError, SmoothlyError};
+
+/// How files are placed into an output directory
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkMode {
+    Copy,
+    Hardlink,
+    Reflink,
+}
+
+impl LinkMode {
+    pub fn from_name(name: &str) -> Result<Self, SmoothlyError> {
+        match name {
+            "copy" => Ok(LinkMode::Copy),
+            "hardlink" => Ok(LinkMode::Hardlink),
+            "reflink" => Ok(LinkMode::Reflink),
+            _ => Err(SmoothlyError::GENERIC(format!("Unknown link mode `{}`", name))),
+        }
+    }
+}
+
+/// Place `src` at `dst`, copying it when a link can't be made
+///
+/// Returns the mode that was actually used.
+pub fn transfer(src: &Path, dst: &Path, mode: LinkMode) -> Result<LinkMode, SmoothlyError> {
+    remove(dst)?;
+    let linked = match mode {
+        LinkMode::Copy => Err(io::Error::new(io::ErrorKind::Other, "copy requested")),
+        LinkMode::Hardlink => std::fs::hard_link(src, dst),
+        LinkMode::Reflink => reflink(src, dst),
+    };
+    if linked.is_ok() {
+        return Ok(mode);
+    }
+    remove(dst)?;
+    std::fs::copy(src, dst).map_err(|e| SmoothlyError::IOPath(IOPathError {
+        source: e,
+        path: src.to_path_buf(),
+        message: None,
+    }))?;
+    Ok(LinkMode::Copy)
+}
+
+/// Recreate the directory `src` at `dst`
+///
+/// Once a link fails, for example because `dst` is on another filesystem,
+/// the remaining files are copied without trying to link them again.
+pub fn transfer_dir(src: &Path, dst: &Path, mode: LinkMode, ignore: &[String], progress: &Progress) -> Result<LinkMode, SmoothlyError> {
+    let mut mode = mode;
+    let walker = walkdir::WalkDir::new(src).into_iter()
+        .filter_entry(|e| !ignored(e.path().strip_prefix(src).unwrap(), ignore));
+    for entry in walker {
+        let entry = entry.map_err(|e| SmoothlyError::GENERIC(e.to_string()))?;
+        let target = dst.join(entry.path().strip_prefix(src).unwrap());
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            progress.start_file(&entry.file_name().to_string_lossy(), size);
+            mode = transfer(entry.path(), &target, mode)?;
+            progress.end_file();
+        }
+    }
+    Ok(mode)
+}
+
+/// Whether `path`, relative to a mod folder, matches one of the ignore patterns
+///
+/// Patterns with a `/` match the whole path, others any single file or folder name.
+/// `*` matches any run of characters and `?` a single one.
+pub fn ignored(path: &Path, patterns: &[String]) -> bool {
+    let names: Vec<String> = path.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+    if names.is_empty() {
+        return false;
+    }
+    let full = names.join("/");
+    patterns.iter().any(|p| {
+        if p.contains('/') {
+            glob(p.trim_matches('/'), &full)
+        } else {
+            names.iter().any(|n| glob(p, n))
+        }
+    })
+}
+
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((sp, st)) = star {
+            // Let the last star take one more character
+            p = sp + 1;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Create a file for writing without truncating an existing one in place
+///
+/// Files in an output directory may be hardlinks into the source tree,
+/// writing through them would also change the source.
+pub fn create(path: &Path) -> Result<File, SmoothlyError> {
+    remove(path)?;
+    Ok(File::create(path)?)
+}
+
+fn remove(path: &Path) -> Result<(), SmoothlyError> {
+    match std::fs::remove_file(path) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(SmoothlyError::IOPath(IOPathError {
+            source: e,
+            path: path.to_path_buf(),
+            message: None,
+        })),
+        Ok(_) => Ok(()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(src: &Path, dst: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+    // _IOW(0x94, 9, int)
+    const FICLONE: u32 = 0x4004_9409;
+    let from = File::open(src)?;
+    let to = File::create(dst)?;
+    if unsafe { libc::ioctl(to.as_raw_fd(), FICLONE as _, from.as_raw_fd()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_: &Path, _: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "reflinks are not supported on this platform"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob("*.bak", "config.bak"));
+        assert!(glob("*.bak", ".bak"));
+        assert!(!glob("*.bak", "config.bak.pbo"));
+        assert!(glob("addons/*", "addons/main.pbo"));
+        assert!(glob("?.txt", "a.txt"));
+        assert!(!glob("?.txt", "ab.txt"));
+        assert!(glob("*a*b*", "xxaxxbxx"));
+        assert!(glob("*", ""));
+        assert!(!glob("a", ""));
+    }
+}
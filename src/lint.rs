@@ -0,0 +1,139 @@
+use std::path::PathBuf;
+
+use hashbrown::HashMap;
+
+use crate::{version, Repo, SmoothlyError};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub level: Level,
+    pub message: String,
+}
+
+impl Issue {
+    fn warning(message: String) -> Self {
+        Self { level: Level::Warning, message }
+    }
+
+    fn error(message: String) -> Self {
+        Self { level: Level::Error, message }
+    }
+}
+
+const REPO_KEYS: &[&str] = &["repoName", "clientParameters", "basePath", "requiredMods", "optionalMods", "servers", "version", "imageChecksum"];
+const MOD_KEYS: &[&str] = &["modName", "Enabled", "checkSum"];
+const SERVER_KEYS: &[&str] = &["name", "address", "port", "password", "battleEye"];
+
+/// Check a repo.json for problems Swifty clients would run into
+pub fn lint(path: &str) -> Result<Vec<Issue>, SmoothlyError> {
+    let text = std::fs::read_to_string(path)?;
+    let mut issues = Vec::new();
+
+    let value: serde_json::Value = serde_json::from_str(&text)?;
+    let value = match version::migrate(value) {
+        Ok(migrated) => {
+            for note in migrated.notes {
+                issues.push(Issue::warning(note));
+            }
+            migrated.value
+        },
+        Err(e) => {
+            issues.push(Issue::error(e.to_string()));
+            return Ok(issues);
+        },
+    };
+    unknown_keys(&value, REPO_KEYS, "repository", &mut issues);
+    for list in &["requiredMods", "optionalMods"] {
+        if let Some(mods) = value.get(list).and_then(|m| m.as_array()) {
+            for m in mods {
+                let name = m.get("modName").and_then(|n| n.as_str()).unwrap_or("?");
+                unknown_keys(m, MOD_KEYS, &format!("mod {}", name), &mut issues);
+            }
+        }
+    }
+    if let Some(servers) = value.get("servers").and_then(|s| s.as_array()) {
+        for s in servers {
+            let name = s.get("name").and_then(|n| n.as_str()).unwrap_or("?");
+            unknown_keys(s, SERVER_KEYS, &format!("server {}", name), &mut issues);
+        }
+    }
+
+    let repo: Repo = serde_json::from_value(value)?;
+
+    let mut seen: HashMap<String, Vec<(&str, String)>> = HashMap::new();
+    for (list, mods) in &[("requiredMods", &repo.requiredMods), ("optionalMods", &repo.optionalMods)] {
+        for m in mods.iter() {
+            seen.entry(m.modName.to_lowercase()).or_insert_with(Vec::new).push((*list, m.modName.clone()));
+        }
+    }
+    let mut groups: Vec<&Vec<(&str, String)>> = seen.values().filter(|g| g.len() > 1).collect();
+    groups.sort_by(|a, b| a[0].1.cmp(&b[0].1));
+    for group in groups {
+        let names: Vec<&String> = group.iter().map(|(_, n)| n).collect();
+        if names.iter().any(|n| *n != names[0]) {
+            issues.push(Issue::error(format!("Mod names only differ by case: {}", names.iter().map(|n| n.as_str()).collect::<Vec<_>>().join(", "))));
+        } else if group.iter().any(|(l, _)| *l != group[0].0) {
+            issues.push(Issue::error(format!("{} is listed in both requiredMods and optionalMods", names[0])));
+        } else {
+            issues.push(Issue::error(format!("{} is listed {} times in {}", names[0], group.len(), group[0].0)));
+        }
+    }
+
+    for m in repo.mods() {
+        if let Some(reason) = invalid_name(&m.modName) {
+            issues.push(Issue::error(format!("{} is not a valid Windows folder name: {}", m.modName, reason)));
+        }
+        if !PathBuf::from(&repo.basePath).join(&m.modName).is_dir() {
+            issues.push(Issue::warning(format!("{} has no folder in {}", m.modName, repo.basePath)));
+        }
+    }
+
+    for server in &repo.servers {
+        match server.port.parse::<u32>() {
+            Ok(p) if p >= 1 && p <= 65535 => {},
+            Ok(p) => issues.push(Issue::error(format!("Server {} has port {} which is out of range", server.name, p))),
+            Err(_) => issues.push(Issue::error(format!("Server {} has a non-numeric port `{}`", server.name, server.port))),
+        }
+        if server.battleEye && server.password.is_empty() {
+            issues.push(Issue::warning(format!("Server {} uses BattlEye without a password", server.name)));
+        }
+    }
+
+    Ok(issues)
+}
+
+fn unknown_keys(value: &serde_json::Value, known: &[&str], what: &str, issues: &mut Vec<Issue>) {
+    if let Some(object) = value.as_object() {
+        for key in object.keys() {
+            if !known.contains(&key.as_str()) {
+                issues.push(Issue::warning(format!("Unknown key `{}` in {}", key, what)));
+            }
+        }
+    }
+}
+
+/// Why `name` can't be used as a folder name on Windows
+fn invalid_name(name: &str) -> Option<String> {
+    if name.is_empty() {
+        return Some("it is empty".to_owned());
+    }
+    if let Some(c) = name.chars().find(|c| "<>:\"/\\|?*".contains(*c) || c.is_control()) {
+        return Some(format!("it contains `{}`", c.escape_default()));
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Some("it ends with a dot or space".to_owned());
+    }
+    let stem = name.split('.').next().unwrap_or("").to_uppercase();
+    let reserved = ["CON", "PRN", "AUX", "NUL"].contains(&stem.as_str())
+        || ((stem.starts_with("COM") || stem.starts_with("LPT")) && stem.len() == 4 && stem.chars().last().map(|c| c.is_ascii_digit() && c != '0').unwrap_or(false));
+    if reserved {
+        return Some(format!("{} is a reserved name", stem));
+    }
+    None
+}
@@ -0,0 +1,195 @@
+//! Exclusive access to an output directory
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{snapshot, SmoothlyError};
+
+/// Locks are considered abandoned after this long, even when their pid is alive as it may have
+/// been reused by another process since
+const STALE_AFTER: u64 = 12 * 60 * 60;
+
+/// Lockfiles that can't be read are considered abandoned after this long
+const UNREADABLE_AFTER: Duration = Duration::from_secs(10);
+
+/// Who holds a lock, as recorded in the lockfile
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Owner {
+    pub host: String,
+    pub pid: u32,
+    pub started: u64,
+}
+
+impl Owner {
+    fn current() -> Self {
+        Self {
+            host: hostname(),
+            pid: std::process::id(),
+            started: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        }
+    }
+
+    /// Whether the process holding the lock is gone, or the lock is too old to trust its pid
+    fn stale(&self) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        if now.saturating_sub(self.started) > STALE_AFTER {
+            return true;
+        }
+        self.host == hostname() && alive(self.pid) == Some(false)
+    }
+}
+
+impl std::fmt::Display for Owner {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "pid {} on {} since {}", self.pid, self.host, snapshot::timestamp(self.started))
+    }
+}
+
+/// Held while a command changes an output directory, released when dropped
+pub struct Lock {
+    path: PathBuf,
+    owner: Owner,
+}
+
+impl Lock {
+    /// Lock `dir`, failing when someone else holds it unless `wait` is set
+    pub fn acquire(dir: &Path, wait: bool) -> Result<Self, SmoothlyError> {
+        let state = dir.join(snapshot::STATE_DIR);
+        std::fs::create_dir_all(&state)?;
+        let path = state.join("lock");
+        let owner = Owner::current();
+        let mut waiting = false;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(serde_json::to_string(&owner)?.as_bytes())?;
+                    file.sync_all()?;
+                    return Ok(Self { path, owner });
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {},
+                Err(e) => return Err(SmoothlyError::from(e)),
+            }
+            // A lockfile that can't be read is being written, or was left half written
+            let holder = read(&path);
+            match holder {
+                Some(ref h) if h.stale() => {
+                    println!("Removing the stale lock of {}", h);
+                    discard(&path, holder.as_ref())?;
+                    continue;
+                },
+                Some(ref h) if !wait => {
+                    return Err(SmoothlyError::GENERIC(format!("{} is locked by {}, use --wait to wait for it", dir.display(), h)));
+                },
+                Some(ref h) if !waiting => {
+                    println!("Waiting for the lock held by {}", h);
+                    waiting = true;
+                },
+                Some(_) => {},
+                None => {
+                    if age(&path) > UNREADABLE_AFTER {
+                        println!("Removing an unreadable lock");
+                        discard(&path, None)?;
+                        continue;
+                    }
+                },
+            }
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Only remove the lockfile if it is still ours
+        if read(&self.path).as_ref() == Some(&self.owner) {
+            std::fs::remove_file(&self.path).ok();
+        }
+    }
+}
+
+fn age(path: &Path) -> Duration {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+        .and_then(|m| m.elapsed().ok())
+        .unwrap_or_default()
+}
+
+fn read(path: &Path) -> Option<Owner> {
+    std::fs::read_to_string(path).ok().and_then(|text| serde_json::from_str(&text).ok())
+}
+
+/// Remove the lockfile at `path` if it still belongs to `expected`
+///
+/// Another process may break the same stale lock and take it between our read and the
+/// removal, so the file is first moved aside, which only one process can do, and put
+/// back when it turns out to be someone else's.
+fn discard(path: &Path, expected: Option<&Owner>) -> Result<(), SmoothlyError> {
+    let aside = path.with_file_name(format!("lock.{}", std::process::id()));
+    match std::fs::rename(path, &aside) {
+        Ok(_) => {},
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(SmoothlyError::from(e)),
+    }
+    let same = match expected {
+        Some(_) => read(&aside).as_ref() == expected,
+        // A new lock may still be half written, only an old one is abandoned
+        None => read(&aside).is_none() && age(&aside) > UNREADABLE_AFTER,
+    };
+    if !same {
+        // Fails if yet another lock was created meanwhile, which then stays in charge
+        std::fs::hard_link(&aside, path).ok();
+    }
+    std::fs::remove_file(&aside)?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_owned())
+        .unwrap_or_else(|_| "unknown".to_owned())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").or_else(|_| std::env::var("HOSTNAME")).unwrap_or_else(|_| "unknown".to_owned())
+}
+
+#[cfg(target_os = "linux")]
+fn alive(pid: u32) -> Option<bool> {
+    // Signal 0 only checks that the process exists
+    Some(unsafe { libc::kill(pid as libc::pid_t, 0) == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM) })
+}
+
+#[cfg(windows)]
+fn alive(pid: u32) -> Option<bool> {
+    use winapi::shared::winerror::ERROR_ACCESS_DENIED;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::minwinbase::STILL_ACTIVE;
+    use winapi::um::processthreadsapi::{GetExitCodeProcess, OpenProcess};
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            // Processes of other users can't be opened, but they exist
+            return Some(io::Error::last_os_error().raw_os_error() == Some(ERROR_ACCESS_DENIED as i32));
+        }
+        let mut code = 0;
+        let queried = GetExitCodeProcess(handle, &mut code);
+        CloseHandle(handle);
+        if queried == 0 {
+            return None;
+        }
+        Some(code == STILL_ACTIVE)
+    }
+}
+
+/// Processes can't be checked here, locks fall back to their age
+#[cfg(not(any(target_os = "linux", windows)))]
+fn alive(_: u32) -> Option<bool> {
+    None
+}
@@ -15,6 +15,9 @@ macro_rules! color {
             },
             Transaction::Existing => {
                 $i.purple()
+            },
+            Transaction::Missing => {
+                $i.yellow()
             }
         }
     };
@@ -34,6 +37,7 @@ macro_rules! state {
 macro_rules! exit {
     ($c:expr, $i:ident) => {
         print!("\r");
+        crossterm::cursor().show().unwrap();
         drop($i);
         std::process::exit($c);
     };
@@ -1,10 +1,12 @@
+use std::path::PathBuf;
+
 use clap;
-use hashbrown::HashMap;
 
 #[cfg(windows)]
 use ansi_term;
 
 use smoothly::error::PrintableError;
+use smoothly::project::{self, Project, Settings};
 use smoothly::Command;
 
 fn main() {
@@ -17,52 +19,58 @@ fn main() {
         version.push_str("-debug");
     }
 
-    let mut app = clap::App::new("smoothly")
-        .version(version.as_ref())
-        .author(env!("CARGO_PKG_AUTHORS"))
-        .about(env!("CARGO_PKG_DESCRIPTION"))
-        .arg(clap::Arg::with_name("repo")
-            .help("Repository file (repo.json)")
-            .global(true)
-            .long("repo")
-        );
-
     let mut commands: Vec<Box<dyn Command>> = Vec::new();
-    let mut hash_commands: HashMap<String, &Box<dyn Command>> = HashMap::new();
 
     commands.push(Box::new(smoothly::commands::New {}));
     //commands.push(Box::new(smoothly::commands::Add {}));
     commands.push(Box::new(smoothly::commands::Interact {}));
     commands.push(Box::new(smoothly::commands::Push {}));
+    commands.push(Box::new(smoothly::commands::Serve {}));
+    commands.push(Box::new(smoothly::commands::Snapshots {}));
+    commands.push(Box::new(smoothly::commands::Rollback {}));
+    commands.push(Box::new(smoothly::commands::Changelog {}));
+    commands.push(Box::new(smoothly::commands::Lint {}));
+    commands.push(Box::new(smoothly::commands::Conflicts {}));
+    commands.push(Box::new(smoothly::commands::Deps {}));
+    commands.push(Box::new(smoothly::commands::Dedupe {}));
+    commands.push(Box::new(smoothly::commands::Size {}));
     commands.push(Box::new(smoothly::commands::SelfUpdate {}));
 
-    for command in commands.iter() {
-        let sub = command.register();
-        let name = sub.get_name().to_owned();
-        app = app.subcommand(sub);
-        hash_commands.insert(name, command);
-    }
-
-    let matches = app.get_matches();
+    let mut argv: Vec<String> = std::env::args().collect();
 
-    let repo = if !matches.is_present("repo") {
-        if std::path::PathBuf::from("repo.json").exists() {
-            "repo.json"
-        } else {
-            println!("No repo specified");
+    // smoothly.toml fills in what the command line leaves out
+    let project = match flag(&argv, "config") {
+        Some(path) => Some(Project::load(&PathBuf::from(path)).unwrap_or_print()),
+        None => Project::discover(flag(&argv, "repo").as_ref().map(|r| r.as_str()))
+            .map(|path| Project::load(&path).unwrap_or_print()),
+    };
+    let target = flag(&argv, "target");
+    let settings = match &project {
+        Some(p) => Some(p.resolve(target.as_ref().map(|t| t.as_str())).unwrap_or_print()),
+        None if target.is_some() => {
+            println!("No {} found for --target", project::FILE);
             std::process::exit(1);
-        }
-    } else {
-        matches.value_of("repo").unwrap()
+        },
+        None => None,
     };
-    println!("Using `{}`", repo);
+    if let Some(settings) = &settings {
+        let names: Vec<String> = commands.iter().map(|c| c.register().get_name().to_owned()).collect();
+        if let Some(i) = subcommand(&argv, &names) {
+            let extra = settings.args(&argv[i], &argv[i + 1..]).unwrap_or_print();
+            argv.extend(extra);
+        }
+    }
+
+    let matches = app(&commands, &version).get_matches_from(&argv);
 
     match matches.subcommand_name() {
         Some(v) => {
-            match hash_commands.get(v) {
+            match commands.iter().find(|c| c.register().get_name() == v) {
                 Some(c) => {
+                    let repo = if c.needs_repo() { find_repo(&matches, settings.as_ref()) } else { String::new() };
                     let sub_matches = matches.subcommand_matches(v).unwrap();
-                    c.run(sub_matches, repo.to_string()).unwrap_or_print();
+                    // The commands fall back to the configured output directory themselves
+                    c.run(sub_matches, repo, &settings.clone().unwrap_or_default()).unwrap_or_print();
                 },
                 None => println!("Unknown Command"),
             }
@@ -71,6 +79,81 @@ fn main() {
     }
 }
 
+fn app<'a>(commands: &'a [Box<dyn Command>], version: &'a str) -> clap::App<'a, 'a> {
+    let mut app = clap::App::new("smoothly")
+        .version(version)
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about(env!("CARGO_PKG_DESCRIPTION"))
+        .arg(clap::Arg::with_name("repo")
+            .help("Repository file (repo.json)")
+            .global(true)
+            .long("repo")
+            .takes_value(true)
+        ).arg(clap::Arg::with_name("config")
+            .help("Project file, defaults to smoothly.toml next to the repository")
+            .global(true)
+            .long("config")
+            .takes_value(true)
+        ).arg(clap::Arg::with_name("target")
+            .help("Target from the project file to use")
+            .global(true)
+            .long("target")
+            .takes_value(true)
+        );
+    for command in commands {
+        app = app.subcommand(command.register());
+    }
+    app
+}
+
+/// Value of a global flag, before clap has parsed anything
+fn flag(argv: &[String], name: &str) -> Option<String> {
+    let long = format!("--{}", name);
+    let mut args = argv.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if *arg == long {
+            return args.next().cloned();
+        }
+        if arg.starts_with(&format!("{}=", long)) {
+            return Some(arg[long.len() + 1..].to_owned());
+        }
+    }
+    None
+}
+
+/// Position of the subcommand in `argv`
+fn subcommand(argv: &[String], names: &[String]) -> Option<usize> {
+    let mut i = 1;
+    while i < argv.len() {
+        if ["--repo", "--config", "--target"].contains(&argv[i].as_str()) {
+            i += 2;
+            continue;
+        }
+        if names.contains(&argv[i]) {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+fn find_repo(matches: &clap::ArgMatches, settings: Option<&Settings>) -> String {
+    let repo = match (matches.value_of("repo"), settings.and_then(|s| s.repo.as_ref())) {
+        (Some(repo), _) => repo.to_owned(),
+        (None, Some(repo)) => repo.clone(),
+        (None, None) => {
+            if std::path::PathBuf::from("repo.json").exists() {
+                "repo.json".to_owned()
+            } else {
+                println!("No repo specified");
+                std::process::exit(1);
+            }
+        },
+    };
+    println!("Using `{}`", repo);
+    repo
+}
+
 #[cfg(windows)]
 fn ansi_support() {
     // Attempt to enable ANSI support in terminal
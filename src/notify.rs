@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::srf::{self, Published};
+use crate::SmoothlyError;
+
+/// Describe a publish as a Discord compatible webhook payload
+pub fn payload(old: Option<&Published>, new: &Published) -> serde_json::Value {
+    let mut changed = Vec::new();
+    for arma_mod in new.repo.mods() {
+        let name = &arma_mod.modName;
+        let before = old.and_then(|o| o.addons.get(name)).and_then(|a| a.hash.clone());
+        let after = new.addons.get(name).and_then(|a| a.hash.clone());
+        if before.is_none() {
+            changed.push(format!("+ {}", name));
+        } else if before != after {
+            changed.push(format!("~ {}", name));
+        }
+    }
+    if let Some(old) = old {
+        for arma_mod in old.repo.mods() {
+            if !new.repo.has_mod(&arma_mod.modName) {
+                changed.push(format!("- {}", arma_mod.modName));
+            }
+        }
+    }
+    let delta = new.size() as i64 - old.map(|o| o.size()).unwrap_or(0) as i64;
+
+    let mut mods = if changed.is_empty() { "No mod changes".to_owned() } else { changed.join("\n") };
+    // Discord rejects embed fields over 1024 characters
+    if mods.len() > 1000 {
+        let mut end = 1000;
+        while !mods.is_char_boundary(end) {
+            end -= 1;
+        }
+        mods.truncate(end);
+        mods.push_str("\n...");
+    }
+
+    json!({
+        "username": "Smoothly",
+        "content": format!("**{}** has been updated", new.repo.repoName),
+        "embeds": [{
+            "title": new.repo.repoName,
+            "fields": [
+                { "name": "Changed mods", "value": format!("```diff\n{}\n```", mods) },
+                { "name": "Download size", "value": format!("{} ({})", srf::human(new.size()), srf::human_delta(delta)), "inline": true },
+                { "name": "Version", "value": format!("{} (Smoothly {})", new.repo.version, env!("CARGO_PKG_VERSION")), "inline": true },
+            ],
+        }],
+    })
+}
+
+/// POST `payload` to `url`, retrying with an increasing delay
+pub fn send(url: &str, payload: &serde_json::Value, retries: u32) -> Result<(), SmoothlyError> {
+    let client = reqwest::Client::new();
+    let mut attempt = 0;
+    loop {
+        let error = match client.post(url).json(payload).send() {
+            Ok(ref response) if response.status().is_success() => return Ok(()),
+            Ok(response) => {
+                // Client errors other than rate limiting won't be fixed by trying again
+                if response.status().is_client_error() && response.status().as_u16() != 429 {
+                    return Err(SmoothlyError::GENERIC(format!("Webhook rejected the notification: {}", response.status())));
+                }
+                response.status().to_string()
+            },
+            Err(e) => e.to_string(),
+        };
+        if attempt >= retries {
+            return Err(SmoothlyError::GENERIC(format!("Webhook failed after {} attempts: {}", attempt + 1, error)));
+        }
+        attempt += 1;
+        println!("Webhook failed ({}), retrying", error);
+        // Double the wait up to a minute, a larger shift would overflow
+        std::thread::sleep(Duration::from_secs(1 << attempt.min(6)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+
+    use hashbrown::HashMap;
+
+    use super::*;
+
+    /// Answer one request with each of `statuses`, passing on the JSON bodies
+    fn server(statuses: &'static [u16]) -> (String, Receiver<serde_json::Value>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_lowercase();
+                    if line.is_empty() { break; }
+                    if line.starts_with("content-length:") {
+                        length = line[15..].trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                tx.send(serde_json::from_slice(&body).unwrap()).unwrap();
+                write!(reader.get_mut(), "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn published() -> Published {
+        Published {
+            dir: std::path::PathBuf::new(),
+            repo: serde_json::from_value(json!({
+                "repoName": "Test",
+                "clientParameters": "",
+                "basePath": "",
+                "requiredMods": [],
+                "optionalMods": [],
+                "servers": [],
+            })).unwrap(),
+            addons: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let (url, requests) = server(&[500, 204]);
+        send(&url, &payload(None, &published()), 3).unwrap();
+        let requests: Vec<serde_json::Value> = requests.try_iter().collect();
+        assert_eq!(requests.len(), 2);
+        for request in &requests {
+            assert!(request["embeds"][0]["fields"].as_array().map(|f| !f.is_empty()).unwrap_or(false));
+        }
+    }
+
+    #[test]
+    fn gives_up_on_client_errors() {
+        let (url, requests) = server(&[400, 204]);
+        assert!(send(&url, &payload(None, &published()), 3).is_err());
+        assert_eq!(requests.try_iter().count(), 1);
+    }
+}
@@ -0,0 +1,213 @@
+use std::path::Path;
+
+use crate::SmoothlyError;
+
+/// Dimensions Swifty displays the repository image at
+pub const WIDTH: u32 = 300;
+pub const HEIGHT: u32 = 160;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Read the dimensions from the IHDR chunk of a PNG file
+pub fn dimensions(data: &[u8]) -> Result<(u32, u32), SmoothlyError> {
+    if data.len() < 24 || data[0..8] != SIGNATURE {
+        return Err(SmoothlyError::GENERIC("The repo image is not a PNG file".to_owned()));
+    }
+    // The IHDR chunk always comes first, its data starts with the width and height
+    if &data[12..16] != b"IHDR" {
+        return Err(SmoothlyError::GENERIC("The repo image is a damaged PNG file".to_owned()));
+    }
+    let be = |b: &[u8]| u32::from(b[0]) << 24 | u32::from(b[1]) << 16 | u32::from(b[2]) << 8 | u32::from(b[3]);
+    Ok((be(&data[16..20]), be(&data[20..24])))
+}
+
+/// Write `src`, in any format the image crate can read, to `dst` as a 300x160 PNG
+///
+/// The format is detected from the contents, a JPEG saved as repo.png is common.
+pub fn resize(src: &Path, dst: &Path) -> Result<(), SmoothlyError> {
+    let unreadable = |e: &dyn std::fmt::Display| SmoothlyError::GENERIC(format!("Unable to read {}: {}", src.display(), e));
+    let data = std::fs::read(src).map_err(|e| unreadable(&e))?;
+    let img = image::load_from_memory(&data).map_err(|e| unreadable(&e))?;
+    img.resize_exact(WIDTH, HEIGHT, image::imageops::FilterType::Lanczos3)
+        .save(dst)
+        .map_err(|e| SmoothlyError::GENERIC(format!("Unable to write {}: {}", dst.display(), e)))
+}
+
+/// Render a plain image showing `name`
+pub fn placeholder(name: &str, dst: &Path) -> Result<(), SmoothlyError> {
+    let background = image::Rgb([0x2b, 0x2d, 0x31]);
+    let accent = image::Rgb([0x58, 0x65, 0xf2]);
+    let foreground = image::Rgb([0xff, 0xff, 0xff]);
+    let mut img = image::RgbImage::from_pixel(WIDTH, HEIGHT, background);
+    for x in 0..WIDTH {
+        for y in HEIGHT - 6..HEIGHT {
+            img.put_pixel(x, y, accent);
+        }
+    }
+
+    let margin = 16;
+    // Largest scale at which the name fits
+    let (scale, lines) = [4, 3, 2, 1].iter()
+        .map(|scale| (*scale, wrap(name, ((WIDTH - 2 * margin) / (6 * scale)) as usize)))
+        .find(|(scale, lines)| lines.len() as u32 * 9 * scale <= HEIGHT - 2 * margin)
+        .unwrap_or_else(|| (1, wrap(name, ((WIDTH - 2 * margin) / 6) as usize)));
+
+    let line_height = 9 * scale;
+    let mut y = HEIGHT.saturating_sub(lines.len() as u32 * line_height) / 2;
+    for line in lines {
+        let mut x = WIDTH.saturating_sub(line.chars().count() as u32 * 6 * scale) / 2;
+        for c in line.chars() {
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..5 {
+                    if bits & (0b10000 >> col) == 0 { continue; }
+                    for dx in 0..scale {
+                        for dy in 0..scale {
+                            let (px, py) = (x + col * scale + dx, y + row as u32 * scale + dy);
+                            if px < WIDTH && py < HEIGHT {
+                                img.put_pixel(px, py, foreground);
+                            }
+                        }
+                    }
+                }
+            }
+            x += 6 * scale;
+        }
+        y += line_height;
+    }
+
+    img.save(dst).map_err(|e| SmoothlyError::GENERIC(format!("Unable to write {}: {}", dst.display(), e)))
+}
+
+/// Break `text` into lines of at most `width` characters, on spaces where possible
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = std::cmp::max(1, width);
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::replace(&mut current, String::new()));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if current.is_empty() {
+            current = word;
+        } else if current.chars().count() + 1 + word.chars().count() <= width {
+            current.push(' ');
+            current.push_str(&word);
+        } else {
+            lines.push(std::mem::replace(&mut current, word));
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// 5x7 bitmap of a character, rows top to bottom
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        ' ' => [0; 7],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '_' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '!' => [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100],
+        '\'' => [0b00100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000],
+        '&' => [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101],
+        '(' => [0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010],
+        ')' => [0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000],
+        '/' => [0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000],
+        '+' => [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::new(width, height))
+            .write_to(&mut data, image::ImageOutputFormat::PNG)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn dimensions_of_png() {
+        assert_eq!(dimensions(&png(300, 160)).unwrap(), (300, 160));
+    }
+
+    #[test]
+    fn dimensions_bad_signature() {
+        let mut data = png(300, 160);
+        data[1] = b'J';
+        assert!(dimensions(&data).is_err());
+    }
+
+    #[test]
+    fn dimensions_missing_ihdr() {
+        let mut data = png(300, 160);
+        data[12..16].copy_from_slice(b"IDAT");
+        assert!(dimensions(&data).is_err());
+        assert!(dimensions(&data[..20]).is_err());
+    }
+
+    #[test]
+    fn resize_jpeg_named_png() {
+        let dir = std::env::temp_dir().join(format!("smoothly-png-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (src, dst) = (dir.join("repo.png"), dir.join("resized.png"));
+        let mut jpeg = Vec::new();
+        image::DynamicImage::ImageRgb8(image::RgbImage::new(640, 480))
+            .write_to(&mut jpeg, image::ImageOutputFormat::JPEG(90))
+            .unwrap();
+        std::fs::write(&src, &jpeg).unwrap();
+
+        let resized = resize(&src, &dst).and_then(|_| Ok(std::fs::read(&dst)?));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(dimensions(&resized.unwrap()).unwrap(), (WIDTH, HEIGHT));
+    }
+}
@@ -0,0 +1,123 @@
+//! Progress bars for the long running parts of a push
+
+use std::cell::Cell;
+use std::path::Path;
+use std::thread::JoinHandle;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+/// Overall, current mod and current file progress of one phase
+///
+/// Nothing is drawn when stdout is not a terminal, lines are printed instead.
+pub struct Progress {
+    overall: ProgressBar,
+    addon: ProgressBar,
+    file: ProgressBar,
+    drawn: bool,
+    file_size: Cell<u64>,
+    file_done: Cell<u64>,
+    render: Option<JoinHandle<()>>,
+}
+
+impl Progress {
+    pub fn new(phase: &str, total: u64) -> Self {
+        if !atty::is(atty::Stream::Stdout) {
+            return Self::hidden();
+        }
+        let multi = MultiProgress::new();
+        let overall = multi.add(bar(total, "[{bar:30}] {bytes}/{total_bytes} {bytes_per_sec} ETA {eta} {msg}"));
+        overall.set_message(phase);
+        let addon = multi.add(bar(0, "[{bar:30}] {bytes}/{total_bytes} {msg}"));
+        let file = multi.add(bar(0, "[{bar:30}] {bytes}/{total_bytes} {msg}"));
+        // Bars are only drawn while something waits on them
+        let render = std::thread::spawn(move || { multi.join().ok(); });
+        Self {
+            overall,
+            addon,
+            file,
+            drawn: true,
+            file_size: Cell::new(0),
+            file_done: Cell::new(0),
+            render: Some(render),
+        }
+    }
+
+    /// Progress that is never shown, for callers without a terminal to report to
+    pub fn hidden() -> Self {
+        Self {
+            overall: ProgressBar::hidden(),
+            addon: ProgressBar::hidden(),
+            file: ProgressBar::hidden(),
+            drawn: false,
+            file_size: Cell::new(0),
+            file_done: Cell::new(0),
+            render: None,
+        }
+    }
+
+    pub fn start_mod(&self, name: &str, size: u64) {
+        if !self.drawn {
+            println!(" - {}", name);
+        }
+        self.addon.set_message(name);
+        self.addon.set_length(size);
+        self.addon.set_position(0);
+    }
+
+    pub fn start_file(&self, name: &str, size: u64) {
+        self.file.set_message(name.rsplit(|c| c == '\\' || c == '/').next().unwrap_or(name));
+        self.file.set_length(size);
+        self.file.set_position(0);
+        self.file_size.set(size);
+        self.file_done.set(0);
+    }
+
+    pub fn advance(&self, bytes: u64) {
+        self.file_done.set(self.file_done.get() + bytes);
+        self.file.inc(bytes);
+        self.addon.inc(bytes);
+        self.overall.inc(bytes);
+    }
+
+    /// Count what is left of the current file, when it was handled without reading all of it
+    pub fn end_file(&self) {
+        let left = self.file_size.get().saturating_sub(self.file_done.get());
+        self.advance(left);
+    }
+
+    /// Print a line without breaking the bars
+    pub fn println(&self, line: &str) {
+        if self.drawn {
+            self.overall.println(line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.file.finish_and_clear();
+        self.addon.finish_and_clear();
+        self.overall.finish_and_clear();
+        if let Some(render) = self.render.take() {
+            render.join().ok();
+        }
+    }
+}
+
+fn bar(length: u64, template: &str) -> ProgressBar {
+    let bar = ProgressBar::new(length);
+    bar.set_style(ProgressStyle::default_bar().template(template).progress_chars("=> "));
+    bar
+}
+
+/// Size of every file in `path`
+pub fn size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path).into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
@@ -0,0 +1,208 @@
+//! Project defaults from smoothly.toml
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::{hooks, SmoothlyError};
+
+pub const FILE: &str = "smoothly.toml";
+
+/// Defaults for the whole project or one of its targets
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Settings {
+    /// Repository file
+    pub repo: Option<String>,
+    /// Output directory for commands that take one
+    pub output: Option<String>,
+    /// Patterns of files and folders that are never pushed
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Flag values by command and long flag name
+    #[serde(default)]
+    pub options: BTreeMap<String, BTreeMap<String, toml::Value>>,
+    /// Commands run around a push, by hook name
+    #[serde(default)]
+    pub hooks: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Project {
+    #[serde(flatten)]
+    pub settings: Settings,
+    #[serde(default)]
+    pub targets: BTreeMap<String, Settings>,
+    /// Folder of smoothly.toml, relative paths in it start there
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+impl Project {
+    pub fn load(path: &Path) -> Result<Self, SmoothlyError> {
+        let text = std::fs::read_to_string(path)?;
+        let mut project: Project = toml::from_str(&text)
+            .map_err(|e| SmoothlyError::GENERIC(format!("Invalid {}: {}", path.display(), e)))?;
+        project.dir = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+        Ok(project)
+    }
+
+    /// smoothly.toml next to the repository file, or in the working directory
+    pub fn discover(repo: Option<&str>) -> Option<PathBuf> {
+        let dir = match repo {
+            Some(r) => PathBuf::from(r).parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+            None => PathBuf::new(),
+        };
+        let path = if dir.as_os_str().is_empty() { PathBuf::from(FILE) } else { dir.join(FILE) };
+        if path.exists() { Some(path) } else { None }
+    }
+
+    /// The project settings with those of `target` on top
+    pub fn resolve(&self, target: Option<&str>) -> Result<Settings, SmoothlyError> {
+        let mut settings = self.settings.clone();
+        if let Some(name) = target {
+            let target = self.targets.get(name)
+                .ok_or_else(|| SmoothlyError::GENERIC(format!("No target `{}` in {}", name, FILE)))?;
+            if target.repo.is_some() { settings.repo = target.repo.clone(); }
+            if target.output.is_some() { settings.output = target.output.clone(); }
+            settings.ignore.extend(target.ignore.iter().cloned());
+            for (hook, commands) in &target.hooks {
+                settings.hooks.insert(hook.clone(), commands.clone());
+            }
+            for (command, options) in &target.options {
+                let merged = settings.options.entry(command.clone()).or_insert_with(BTreeMap::new);
+                for (key, value) in options {
+                    merged.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        settings.repo = settings.repo.map(|r| self.path(&r));
+        settings.output = settings.output.map(|o| self.path(&o));
+        Ok(settings)
+    }
+
+    fn path(&self, path: &str) -> String {
+        self.dir.join(path).to_string_lossy().into_owned()
+    }
+}
+
+impl Settings {
+    /// Command line arguments for the options of `command` that `given` doesn't set already
+    pub fn args(&self, command: &str, given: &[String]) -> Result<Vec<String>, SmoothlyError> {
+        let mut options: Vec<(String, toml::Value)> = self.options.get(command)
+            .map(|o| o.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default();
+        // Ignore rules and hooks only apply to push
+        if command == "push" {
+            if !self.ignore.is_empty() {
+                let ignore = self.ignore.iter().map(|i| toml::Value::String(i.clone())).collect();
+                options.push(("ignore".to_owned(), toml::Value::Array(ignore)));
+            }
+            for (hook, commands) in &self.hooks {
+                if !hooks::ALL.contains(&hook.as_str()) {
+                    return Err(SmoothlyError::GENERIC(format!("Unknown hook `{}` in {}, use one of {}", hook, FILE, hooks::ALL.join(", "))));
+                }
+                options.push((hook.clone(), commands.clone()));
+            }
+        }
+        let mut args = Vec::new();
+        for (key, value) in options {
+            let flag = format!("--{}", key);
+            if given.iter().any(|g| *g == flag || g.starts_with(&format!("{}=", flag))) {
+                continue;
+            }
+            let values: Vec<String> = match value {
+                toml::Value::Boolean(false) => continue,
+                toml::Value::Boolean(true) => {
+                    args.push(flag);
+                    continue;
+                },
+                toml::Value::Array(a) => a.into_iter().map(|v| scalar(&key, v)).collect::<Result<_, _>>()?,
+                v => vec![scalar(&key, v)?],
+            };
+            for value in values {
+                args.push(flag.clone());
+                args.push(value);
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn scalar(key: &str, value: toml::Value) -> Result<String, SmoothlyError> {
+    match value {
+        toml::Value::String(s) => Ok(s),
+        toml::Value::Integer(i) => Ok(i.to_string()),
+        toml::Value::Float(f) => Ok(f.to_string()),
+        _ => Err(SmoothlyError::GENERIC(format!("Unsupported value for `{}` in {}", key, FILE))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Settings {
+        toml::from_str(text).unwrap()
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| (*a).to_owned()).collect()
+    }
+
+    #[test]
+    fn options() {
+        let settings = parse(r#"
+            [options.push]
+            link-mode = "hardlink"
+            keep-snapshots = 5
+            wait = true
+            snapshot = false
+            with = ["a.json", "b.json"]
+        "#);
+        assert_eq!(settings.args("push", &[]).unwrap(), args(&[
+            "--keep-snapshots", "5",
+            "--link-mode", "hardlink",
+            "--wait",
+            "--with", "a.json", "--with", "b.json",
+        ]));
+        assert!(settings.args("serve", &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn given_flags_win() {
+        let settings = parse(r#"
+            [options.push]
+            link-mode = "hardlink"
+            wait = true
+        "#);
+        assert_eq!(settings.args("push", &args(&["--link-mode=copy"])).unwrap(), args(&["--wait"]));
+        assert_eq!(settings.args("push", &args(&["--link-mode", "copy", "--wait"])).unwrap(), args(&[]));
+    }
+
+    #[test]
+    fn ignore_and_hooks() {
+        let settings = parse(r#"
+            ignore = ["*.bak"]
+            [hooks]
+            pre-push = "make"
+        "#);
+        assert_eq!(settings.args("push", &[]).unwrap(), args(&["--ignore", "*.bak", "--pre-push", "make"]));
+        assert!(settings.args("serve", &[]).unwrap().is_empty());
+
+        let settings = parse(r#"
+            [hooks]
+            pre-pull = "make"
+        "#);
+        assert!(settings.args("push", &[]).is_err());
+    }
+
+    #[test]
+    fn unsupported_value() {
+        let settings = parse(r#"
+            [options.push]
+            with = { a = 1 }
+        "#);
+        assert!(settings.args("push", &[]).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+//! Turning a source repository into the repo.json players download
+
+use serde_json::{Map, Value};
+
+use crate::{version, Repo, Server, SmoothlyError};
+
+pub struct Options {
+    /// Replaces the local basePath, which means nothing to players
+    pub base_path: String,
+    /// Whether server passwords are published
+    pub passwords: bool,
+}
+
+/// The published contents of `repo` in the `format` version
+pub fn render(repo: &Repo, format: &str, options: &Options) -> Result<String, SmoothlyError> {
+    let mut repo = repo.clone();
+    repo.basePath = options.base_path.clone();
+    for server in &mut repo.servers {
+        server.password = if options.passwords {
+            password(server)?
+        } else {
+            String::new()
+        };
+    }
+    let mut value = serde_json::to_value(&repo)?;
+    version::downgrade(&mut value, format)?;
+    Ok(serde_json::to_string_pretty(&sorted(value))?)
+}
+
+/// Resolve every server password, to fail before anything is published
+pub fn check(repo: &Repo) -> Result<(), SmoothlyError> {
+    for server in &repo.servers {
+        password(server)?;
+    }
+    Ok(())
+}
+
+fn password(server: &Server) -> Result<String, SmoothlyError> {
+    secret(&server.password)
+        .map_err(|e| SmoothlyError::GENERIC(format!("Password of server `{}`: {}", server.name, e)))
+}
+
+/// Resolve a secret reference, `env:NAME` or `file:/path`, other values are used as they are
+///
+/// References are kept in the source repository so it can be committed without the secrets.
+pub fn secret(value: &str) -> Result<String, SmoothlyError> {
+    if value.starts_with("env:") {
+        let name = &value[4..];
+        std::env::var(name).map_err(|_| SmoothlyError::GENERIC(format!("environment variable `{}` is not set", name)))
+    } else if value.starts_with("file:") {
+        let path = &value[5..];
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| SmoothlyError::GENERIC(format!("unable to read `{}`: {}", path, e)))?;
+        Ok(contents.trim_end_matches(|c| c == '\r' || c == '\n').to_owned())
+    } else {
+        Ok(value.to_owned())
+    }
+}
+
+/// Rebuild every object with its keys in order, whatever map serde_json was built with,
+/// so unchanged repositories are published byte for byte the same
+pub fn sorted(value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries: Vec<(String, Value)> = object.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut map = Map::new();
+            for (key, value) in entries {
+                map.insert(key, sorted(value));
+            }
+            Value::Object(map)
+        },
+        Value::Array(items) => Value::Array(items.into_iter().map(sorted).collect()),
+        v => v,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain() {
+        assert_eq!(secret("hunter2").unwrap(), "hunter2");
+        assert_eq!(secret("").unwrap(), "");
+    }
+
+    #[test]
+    fn env() {
+        std::env::set_var("SMOOTHLY_TEST_SECRET", "from env");
+        assert_eq!(secret("env:SMOOTHLY_TEST_SECRET").unwrap(), "from env");
+        assert!(secret("env:SMOOTHLY_TEST_SECRET_UNSET").is_err());
+    }
+
+    #[test]
+    fn file() {
+        let path = std::env::temp_dir().join(format!("smoothly-secret-{}", std::process::id()));
+        std::fs::write(&path, "from file\r\n\n").unwrap();
+        let value = secret(&format!("file:{}", path.display()));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(value.unwrap(), "from file");
+        assert!(secret(&format!("file:{}", path.display())).is_err());
+    }
+}
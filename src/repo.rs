@@ -1,7 +1,11 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 use serde_json;
 
-use crate::{SmoothlyError, State};
+use crate::{version, SmoothlyError, State};
 
 #[allow(non_snake_case)]
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -33,7 +37,7 @@ pub struct Mod {
 }
 
 #[allow(non_snake_case)]
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Server {
     pub name: String,
     pub address: String,
@@ -44,7 +48,36 @@ pub struct Server {
 
 impl Repo {
     pub fn new(name: String) -> Result<Self, SmoothlyError> {
-        serde_json::from_str(&std::fs::read_to_string(name)?).map_err(SmoothlyError::from)
+        let value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(name)?)?;
+        let migrated = version::migrate(value)?;
+        for note in &migrated.notes {
+            println!("{}", note);
+        }
+        serde_json::from_value(migrated.value).map_err(SmoothlyError::from)
+    }
+
+    /// Write the repository to `path`, keeping the previous file as `<path>.bak`
+    ///
+    /// The new contents are written to a temporary file first and moved into place,
+    /// so an interrupted write never leaves a truncated repo.json behind.
+    pub fn save(&self, path: &str) -> Result<(), SmoothlyError> {
+        let j = serde_json::to_string_pretty(&self)?;
+        let tmp = format!("{}.tmp", path);
+        {
+            let mut fout = File::create(&tmp)?;
+            fout.write_all(j.as_bytes())?;
+            fout.sync_all()?;
+        }
+        if PathBuf::from(path).exists() {
+            std::fs::copy(path, format!("{}.bak", path))?;
+        }
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Every mod in the repository, required mods first
+    pub fn mods(&self) -> impl Iterator<Item = &Mod> {
+        self.requiredMods.iter().chain(self.optionalMods.iter())
     }
 
     pub fn has_mod(&self, name: &str) -> bool {
@@ -75,6 +108,19 @@ impl Repo {
         State::Disabled
     }
 
+    pub fn remove_mod(&mut self, name: &str) {
+        self.requiredMods.retain(|m| m.modName != name);
+        self.optionalMods.retain(|m| m.modName != name);
+    }
+
+    /// Mods listed in the repository that have no folder in `basePath`
+    pub fn missing_mods(&self) -> Vec<String> {
+        self.mods()
+            .filter(|m| !PathBuf::from(&self.basePath).join(&m.modName).is_dir())
+            .map(|m| m.modName.clone())
+            .collect()
+    }
+
     pub fn set_hash(&mut self, name: &str, hash: String) {
         for arma_mod in &mut self.requiredMods {
             if arma_mod.modName == name {
@@ -92,5 +138,5 @@ impl Repo {
 }
 
 pub fn default_version() -> String {
-    "3.0.0.0".to_owned()
+    version::CURRENT.to_owned()
 }
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::progress::Progress;
+use crate::{link, LinkMode, SmoothlyError};
+
+/// Directory inside an output directory where Smoothly keeps its own state
+pub const STATE_DIR: &str = ".smoothly";
+
+/// A published state of an output directory
+///
+/// Files are hardlinked into the snapshot. Push replaces changed files instead of writing
+/// into them, so they keep their contents when the output directory changes. Files pushed
+/// with `--link-mode hardlink` are also hardlinks into basePath though, editing one of those
+/// in place changes every snapshot holding it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    pub id: String,
+    pub created: u64,
+    pub repos: Vec<String>,
+}
+
+impl Snapshot {
+    /// Directory holding the snapshotted files
+    pub fn files(&self, dir: &Path) -> PathBuf {
+        snapshots_dir(dir).join(&self.id).join("files")
+    }
+
+    pub fn created(&self) -> String {
+        timestamp(self.created)
+    }
+}
+
+pub fn snapshots_dir(dir: &Path) -> PathBuf {
+    dir.join(STATE_DIR).join("snapshots")
+}
+
+/// Record the current contents of `dir`
+pub fn create(dir: &Path, repos: Vec<String>) -> Result<Snapshot, SmoothlyError> {
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let base = id(created);
+    let mut id = base.clone();
+    let mut n = 1;
+    while snapshots_dir(dir).join(&id).exists() {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    let snapshot = Snapshot { id, created, repos };
+    let files = snapshot.files(dir);
+    std::fs::create_dir_all(&files)?;
+    // Copies would take the size of the whole output for every snapshot
+    let unsupported = || {
+        std::fs::remove_dir_all(snapshots_dir(dir).join(&snapshot.id)).ok();
+        SmoothlyError::GENERIC(format!("Snapshots need hardlinks, which {} doesn't support", dir.display()))
+    };
+    let entries = published(dir)?;
+    if let Some(file) = entries.iter()
+        .flat_map(|e| walkdir::WalkDir::new(e).into_iter().filter_map(|f| f.ok()))
+        .find(|f| f.file_type().is_file()) {
+        let probe = files.join(".probe");
+        let linked = std::fs::hard_link(file.path(), &probe);
+        std::fs::remove_file(&probe).ok();
+        if linked.is_err() {
+            return Err(unsupported());
+        }
+    }
+    for entry in entries {
+        let target = files.join(entry.file_name().unwrap());
+        let used = if entry.is_dir() {
+            link::transfer_dir(&entry, &target, LinkMode::Hardlink, &[], &Progress::hidden())?
+        } else {
+            link::transfer(&entry, &target, LinkMode::Hardlink)?
+        };
+        if used != LinkMode::Hardlink {
+            return Err(unsupported());
+        }
+    }
+    let j = serde_json::to_string_pretty(&snapshot)?;
+    std::fs::write(snapshots_dir(dir).join(&snapshot.id).join("snapshot.json"), j)?;
+    Ok(snapshot)
+}
+
+/// Snapshots of `dir`, oldest first
+pub fn list(dir: &Path) -> Result<Vec<Snapshot>, SmoothlyError> {
+    let mut snapshots = Vec::new();
+    let root = snapshots_dir(dir);
+    if !root.exists() {
+        return Ok(snapshots);
+    }
+    for entry in std::fs::read_dir(root)? {
+        let meta = entry?.path().join("snapshot.json");
+        if !meta.exists() { continue; }
+        let snapshot: Snapshot = serde_json::from_str(&std::fs::read_to_string(meta)?)?;
+        snapshots.push(snapshot);
+    }
+    snapshots.sort_by(|a, b| (a.created, &a.id).cmp(&(b.created, &b.id)));
+    Ok(snapshots)
+}
+
+pub fn find(dir: &Path, id: &str) -> Result<Snapshot, SmoothlyError> {
+    list(dir)?.into_iter().find(|s| s.id == id)
+        .ok_or_else(|| SmoothlyError::GENERIC(format!("No snapshot `{}` in {}", id, dir.display())))
+}
+
+/// Delete all but the `keep` newest snapshots
+pub fn prune(dir: &Path, keep: usize) -> Result<Vec<Snapshot>, SmoothlyError> {
+    let mut snapshots = list(dir)?;
+    let remove = snapshots.len().saturating_sub(keep);
+    let removed: Vec<Snapshot> = snapshots.drain(..remove).collect();
+    for snapshot in &removed {
+        std::fs::remove_dir_all(snapshots_dir(dir).join(&snapshot.id))?;
+    }
+    Ok(removed)
+}
+
+/// Replace the published contents of `dir` with `snapshot`
+///
+/// The snapshot is staged next to the output first, so the only window where
+/// clients can see a mix of both states is while the top level entries are renamed.
+/// If anything fails during the swap the previous contents are moved back.
+pub fn restore(dir: &Path, snapshot: &Snapshot) -> Result<(), SmoothlyError> {
+    let state = dir.join(STATE_DIR);
+    let staging = state.join("staging");
+    let trash = state.join("trash");
+    for d in &[&staging, &trash] {
+        if d.exists() {
+            std::fs::remove_dir_all(d)?;
+        }
+    }
+    link::transfer_dir(&snapshot.files(dir), &staging, LinkMode::Hardlink, &[], &Progress::hidden())?;
+    std::fs::create_dir_all(&trash)?;
+
+    let current = published(dir)?;
+    let swap = || -> Result<(), SmoothlyError> {
+        for entry in &current {
+            std::fs::rename(entry, trash.join(entry.file_name().unwrap()))?;
+        }
+        for entry in std::fs::read_dir(&staging)? {
+            let entry = entry?;
+            std::fs::rename(entry.path(), dir.join(entry.file_name()))?;
+        }
+        Ok(())
+    };
+    if let Err(e) = swap() {
+        for entry in std::fs::read_dir(&trash)? {
+            let entry = entry?;
+            let target = dir.join(entry.file_name());
+            if target.is_dir() {
+                std::fs::remove_dir_all(&target)?;
+            } else if target.exists() {
+                std::fs::remove_file(&target)?;
+            }
+            std::fs::rename(entry.path(), target)?;
+        }
+        return Err(e);
+    }
+    std::fs::remove_dir_all(&trash)?;
+    std::fs::remove_dir_all(&staging)?;
+    Ok(())
+}
+
+/// Top level entries of `dir` that are published to clients
+fn published(dir: &Path) -> Result<Vec<PathBuf>, SmoothlyError> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name() == STATE_DIR { continue; }
+        entries.push(entry.path());
+    }
+    Ok(entries)
+}
+
+fn id(secs: u64) -> String {
+    let (y, m, d, hh, mm, ss) = civil(secs);
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", y, m, d, hh, mm, ss)
+}
+
+pub(crate) fn timestamp(secs: u64) -> String {
+    let (y, m, d, hh, mm, ss) = civil(secs);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC", y, m, d, hh, mm, ss)
+}
+
+/// Split a unix timestamp into UTC date and time
+fn civil(secs: u64) -> (i64, u64, u64, u64, u64, u64) {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // Howard Hinnant's days_from_civil, in reverse
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u64;
+    let m = (if mp < 10 { mp + 3 } else { mp - 9 }) as u64;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d, rem / 3600, rem % 3600 / 60, rem % 60)
+}
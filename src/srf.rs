@@ -0,0 +1,357 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use hashbrown::HashMap;
+use md5::{Md5, Digest};
+use pbo::PBO;
+
+use crate::progress::Progress;
+use crate::{snapshot, Addon, FilePart, Repo, SmoothlyError, SwiftyFile};
+
+/// Parse the contents of a mod.srf file
+pub fn parse(text: &str) -> Result<Addon, SmoothlyError> {
+    let invalid = |line: &str| SmoothlyError::GENERIC(format!("Invalid SRF line `{}`", line));
+    let mut lines = text.lines().filter(|l| !l.is_empty());
+
+    let header = lines.next().ok_or_else(|| SmoothlyError::GENERIC("Empty SRF file".to_owned()))?;
+    if !header.starts_with("ADDON:") {
+        return Err(invalid(header));
+    }
+    let fields: Vec<&str> = header[6..].rsplitn(3, ':').collect();
+    if fields.len() != 3 {
+        return Err(invalid(header));
+    }
+    let mut addon = Addon::new(fields[2].to_owned());
+    addon.hash = Some(fields[0].to_owned());
+
+    while let Some(line) = lines.next() {
+        let mut kind = line.splitn(2, ':');
+        match kind.next() {
+            Some("PBO") | Some("FILE") => {},
+            _ => return Err(invalid(line)),
+        }
+        let fields: Vec<&str> = kind.next().ok_or_else(|| invalid(line))?.rsplitn(4, ':').collect();
+        if fields.len() != 4 {
+            return Err(invalid(line));
+        }
+        let mut file = SwiftyFile::new(fields[3].to_owned());
+        file.hash = Some(fields[0].to_owned());
+        let parts: usize = fields[1].parse().map_err(|_| invalid(line))?;
+        for _ in 0..parts {
+            let line = lines.next().ok_or_else(|| SmoothlyError::GENERIC(format!("Missing parts for {}", file.name)))?;
+            let fields: Vec<&str> = line.rsplitn(4, ':').collect();
+            if fields.len() != 4 {
+                return Err(invalid(line));
+            }
+            file.parts.push(FilePart {
+                name: fields[3].to_owned(),
+                start: fields[2].parse().map_err(|_| invalid(line))?,
+                size: fields[1].parse().map_err(|_| invalid(line))?,
+                hash: fields[0].to_owned(),
+            });
+        }
+        addon.files.push(file);
+    }
+    Ok(addon)
+}
+
+pub fn read(path: &Path) -> Result<Addon, SmoothlyError> {
+    parse(&std::fs::read_to_string(path)?)
+}
+
+/// Hash every file in `moddir` the way Swifty does
+///
+/// `visit` is called with the relative name of every PBO that is read,
+/// so other checks can share the single pass over the files.
+pub fn generate(moddir: &Path, name: &str, visit: &mut dyn FnMut(&str, &PBO), progress: &Progress) -> Result<Addon, SmoothlyError> {
+    // Files can change while a watched mod is pushed, fail the push rather than panic
+    let unreadable = |path: &Path, e: &dyn std::fmt::Display| SmoothlyError::GENERIC(format!("Unable to read {}: {}", path.display(), e));
+    let mut addon = Addon::new(name.to_owned());
+    for direntry in walkdir::WalkDir::new(moddir).sort_by(|a,b| {
+        if a.path().is_dir() == b.path().is_dir() {
+            b.file_name().cmp(a.file_name())
+        } else {
+            b.path().is_dir().cmp(&a.path().is_dir())
+        }}) {
+        let entry = direntry.map_err(|e| unreadable(moddir, &e))?;
+        let path = entry.path();
+        if path.is_dir() { continue; }
+        let name = relative(moddir, path);
+        let mut swiftyfile = SwiftyFile::new(name.clone());
+        progress.start_file(&name, entry.metadata().map(|m| m.len()).unwrap_or(0));
+        if path.extension().unwrap_or_else(|| OsStr::new("")) == OsStr::new("pbo") {
+            let pbo = PBO::read(&mut File::open(path).map_err(|e| unreadable(path, &e))?)
+                .map_err(|e| unreadable(path, &e))?;
+            visit(&name, &pbo);
+            let mut headertotal = Vec::new();
+            headertotal.append(&mut vec!{0});
+            headertotal.append(&mut transform_u32_to_array_of_u8(0x5665_7273).to_vec().into_iter().rev().collect::<Vec<u8>>());
+            headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
+            headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
+            headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
+            headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
+
+            for header in pbo.extension_order {
+                headertotal.append(&mut header.chars().map(|c| c as u8).collect::<Vec<u8>>());
+                headertotal.append(&mut vec!{0});
+                headertotal.append(&mut pbo.extensions.get(&header).unwrap().chars().map(|c| c as u8).collect::<Vec<u8>>());
+                headertotal.append(&mut vec!{0});
+            }
+            headertotal.append(&mut vec!{0});
+            for header in pbo.headers {
+                // 4 bytes * 5 u32 per header
+                headertotal.append(&mut header.filename.chars().map(|c| c as u8).collect::<Vec<u8>>());
+                headertotal.append(&mut vec!{0});
+                headertotal.append(&mut transform_u32_to_array_of_u8(header.method).to_vec().into_iter().rev().collect::<Vec<u8>>());
+                headertotal.append(&mut transform_u32_to_array_of_u8(header.original).to_vec().into_iter().rev().collect::<Vec<u8>>());
+                headertotal.append(&mut transform_u32_to_array_of_u8(header.reserved).to_vec().into_iter().rev().collect::<Vec<u8>>());
+                headertotal.append(&mut transform_u32_to_array_of_u8(header.timestamp).to_vec().into_iter().rev().collect::<Vec<u8>>());
+                headertotal.append(&mut transform_u32_to_array_of_u8(header.size).to_vec().into_iter().rev().collect::<Vec<u8>>());
+            }
+            headertotal.append(&mut vec!{0});
+            headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
+            headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
+            headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
+            headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
+            headertotal.append(&mut transform_u32_to_array_of_u8(0u32).to_vec().into_iter().rev().collect::<Vec<u8>>());
+
+            let header_digest = {
+                let mut hasher = Md5::new();
+                hasher.input(&headertotal);
+                hasher.result()
+            };
+
+            swiftyfile.parts.push(FilePart {
+                name: "$$HEADER$$".to_owned(),
+                size: headertotal.len(),
+                hash: format!("{:X}", header_digest),
+                start: 0,
+            });
+
+            let mut start = headertotal.len();
+
+            for mut file in pbo.files {
+                let mut buffersize = 0;
+                let mut hasher = Md5::new();
+                loop {
+                    let mut buffer = vec![0u8; 4194304];
+                    let read = file.1.read(&mut buffer).map_err(|e| unreadable(path, &e))?;
+                    hasher.input(&buffer[0..read]);
+                    progress.advance(read as u64);
+                    buffersize += read;
+                    if read != 4194304 {
+                        break;
+                    }
+                }
+                swiftyfile.parts.push(FilePart {
+                    name: file.0.clone(),
+                    size: buffersize,
+                    hash: format!("{:X}",hasher.result()),
+                    start,
+                });
+                start += buffersize;
+            }
+            let mut chk = pbo.checksum.ok_or_else(|| unreadable(path, &"missing checksum"))?;
+            chk.insert(0, 0);
+            swiftyfile.parts.push(FilePart {
+                name: "$$END$$".to_owned(),
+                size:  21,
+                hash: format!("{:X}", {
+                        let mut hasher = Md5::new();
+                        hasher.input(&chk);
+                        hasher.result()
+                    }),
+                start,
+            });
+        } else {
+            if path.file_name().unwrap().to_str().unwrap() == "mod.srf" { continue; }
+            let mut f = File::open(path).map_err(|e| unreadable(path, &e))?;
+            let mut buffersize = 0;
+            let mut hasher = Md5::new();
+            loop {
+                let mut buffer = vec![0u8; 4194304];
+                let read = f.read(&mut buffer).map_err(|e| unreadable(path, &e))?;
+                hasher.input(&buffer[0..read]);
+                progress.advance(read as u64);
+                buffersize += read;
+                if read != 4194304 {
+                    break;
+                }
+            }
+            swiftyfile.parts.push(FilePart {
+                name: format!("{}_{}", path.file_name().unwrap().to_str().unwrap().to_owned(), buffersize),
+                hash: format!("{:X}", hasher.result()),
+                size: buffersize as usize,
+                start: 0,
+            });
+        }
+        progress.end_file();
+        addon.files.push(swiftyfile);
+    }
+
+    Ok(addon)
+}
+
+/// Path of `path` inside `moddir`, with the backslashes Swifty uses
+fn relative(moddir: &Path, path: &Path) -> String {
+    let mut name = String::new();
+    for component in path.strip_prefix(moddir).unwrap().components() {
+        if let std::path::Component::Normal(c) = component {
+            name.push_str(&format!("{}\\", c.to_str().unwrap()));
+        }
+    }
+    name.pop();
+    name
+}
+
+/// A published repository, as Swifty clients see it
+pub struct Published {
+    pub dir: PathBuf,
+    pub repo: Repo,
+    pub addons: HashMap<String, Addon>,
+}
+
+impl Published {
+    /// Load `file` and the mod.srf of every mod it lists from `dir`
+    pub fn load(dir: &Path, file: &str) -> Result<Self, SmoothlyError> {
+        let repo = Repo::new(dir.join(file).to_str().unwrap().to_owned())?;
+        let mut addons = HashMap::new();
+        for arma_mod in repo.mods() {
+            let srf = dir.join(&arma_mod.modName).join("mod.srf");
+            if srf.exists() {
+                addons.insert(arma_mod.modName.clone(), read(&srf)?);
+            }
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            repo,
+            addons,
+        })
+    }
+
+    /// Load an output directory, or one of the snapshots of `dir` by id
+    pub fn locate(dir: &Path, location: &str, file: &str) -> Result<Self, SmoothlyError> {
+        let path = PathBuf::from(location);
+        if path.is_dir() {
+            return Self::load(&path, file);
+        }
+        let snapshot = snapshot::find(dir, location)?;
+        Self::load(&snapshot.files(dir), file)
+    }
+
+    /// Total size of every file clients download
+    pub fn size(&self) -> u64 {
+        self.addons.values().map(addon_size).sum()
+    }
+}
+
+pub fn addon_size(addon: &Addon) -> u64 {
+    addon.files.iter().map(|f| f.size() as u64).sum()
+}
+
+/// Bytes of `new` a client holding `old` has to download
+///
+/// Only parts whose name and hash differ are fetched, for plain files that is the whole file.
+pub fn changed_bytes(old: Option<&SwiftyFile>, new: &SwiftyFile) -> u64 {
+    let old = match old {
+        Some(o) => o,
+        None => return new.size() as u64,
+    };
+    new.parts.iter()
+        .filter(|p| !old.parts.iter().any(|o| o.name == p.name && o.hash == p.hash))
+        .map(|p| p.size as u64)
+        .sum()
+}
+
+/// What a player downloads for one mod
+pub struct Estimate {
+    pub addon: String,
+    /// Coming from the previous version
+    pub update: u64,
+    /// Without any previous version
+    pub fresh: u64,
+}
+
+/// Bytes of `new` a client holding `old` has to download, file by file
+pub fn download(old: Option<&Addon>, new: &Addon) -> u64 {
+    new.files.iter()
+        .map(|f| changed_bytes(old.and_then(|o| o.files.iter().find(|p| p.name == f.name)), f))
+        .sum()
+}
+
+/// Download sizes of every mod in `new`, in repository order
+pub fn estimate(old: Option<&Published>, new: &Published) -> Vec<Estimate> {
+    new.repo.mods()
+        .filter_map(|m| new.addons.get(&m.modName).map(|a| (&m.modName, a)))
+        .map(|(name, addon)| Estimate {
+            addon: name.clone(),
+            update: download(old.and_then(|o| o.addons.get(name)), addon),
+            fresh: addon_size(addon),
+        })
+        .collect()
+}
+
+/// Format a number of bytes for people
+pub fn human(bytes: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.2} {}", size, units[unit])
+    }
+}
+
+/// Like `human`, with a sign
+pub fn human_delta(bytes: i64) -> String {
+    format!("{}{}", if bytes < 0 { "-" } else { "+" }, human(bytes.abs() as u64))
+}
+
+fn transform_u32_to_array_of_u8(x:u32) -> [u8;4] {
+    let b1 : u8 = ((x >> 24) & 0xff) as u8;
+    let b2 : u8 = ((x >> 16) & 0xff) as u8;
+    let b3 : u8 = ((x >> 8) & 0xff) as u8;
+    let b4 : u8 = (x & 0xff) as u8;
+    return [b1, b2, b3, b4]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lines() {
+        let addon = parse("ADDON:@ace:2:ABCD\n\
+                           PBO:addons\\ace_main.pbo:30:2:EF01\n\
+                           ace_main.pbo_0:0:20:1111\n\
+                           ace_main.pbo_1:20:10:2222\n\
+                           FILE:meta.cpp:5:1:3333\n\
+                           meta.cpp:0:5:4444\n").unwrap();
+        assert_eq!(addon.name, "@ace");
+        assert_eq!(addon.hash, Some("ABCD".to_owned()));
+        assert_eq!(addon.files.len(), 2);
+        let pbo = &addon.files[0];
+        assert_eq!(pbo.name, "addons\\ace_main.pbo");
+        assert_eq!(pbo.hash, Some("EF01".to_owned()));
+        assert_eq!(pbo.parts.len(), 2);
+        assert_eq!((pbo.parts[1].start, pbo.parts[1].size), (20, 10));
+        assert_eq!(pbo.size(), 30);
+        assert_eq!(addon.files[1].parts[0].hash, "4444");
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(parse("").is_err());
+        assert!(parse("MOD:@ace:0:ABCD\n").is_err());
+        assert!(parse("ADDON:@ace:1:ABCD\nDIR:x:0:0:0\n").is_err());
+        // Fewer part lines than announced
+        assert!(parse("ADDON:@ace:1:ABCD\nFILE:meta.cpp:5:2:3333\nmeta.cpp:0:5:4444\n").is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+use crate::SmoothlyError;
+
+/// repo.json format written by Smoothly
+pub const CURRENT: &str = "3.0.0.0";
+/// Format of documents without a `version` field, written before the format was versioned
+pub const LEGACY: &str = "2.0.0.0";
+/// Formats Smoothly can read and write
+pub const KNOWN: &[&str] = &[LEGACY, CURRENT];
+
+/// A repo.json document brought up to the current format
+pub struct Migrated {
+    pub value: Value,
+    pub from: String,
+    pub notes: Vec<String>,
+}
+
+fn parse(version: &str) -> Option<Vec<u32>> {
+    version.split('.').map(|p| p.parse().ok()).collect()
+}
+
+fn compare(a: &[u32], b: &[u32]) -> Ordering {
+    let len = std::cmp::max(a.len(), b.len());
+    for i in 0..len {
+        let ord = a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0));
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Detect the format of `value` and migrate it to `CURRENT`
+///
+/// Documents from a newer major version are refused, newer minor versions are read
+/// with a note since their additions are unknown to Smoothly.
+pub fn migrate(mut value: Value) -> Result<Migrated, SmoothlyError> {
+    let from = value.get("version").and_then(|v| v.as_str()).unwrap_or(LEGACY).to_owned();
+    let parsed = parse(&from).ok_or_else(|| SmoothlyError::GENERIC(format!("`{}` is not a valid repo.json version", from)))?;
+    let current = parse(CURRENT).unwrap();
+    let mut notes = Vec::new();
+
+    match compare(&parsed, &current) {
+        Ordering::Greater if parsed[0] > current[0] => {
+            return Err(SmoothlyError::GENERIC(format!("repo.json uses format {}, Smoothly only supports up to {}", from, CURRENT)));
+        },
+        Ordering::Greater => {
+            notes.push(format!("repo.json uses the newer format {}, unknown fields will be dropped when it is written", from));
+        },
+        Ordering::Less => {
+            if compare(&parsed, &parse(LEGACY).unwrap()) != Ordering::Greater {
+                legacy(&mut value, &mut notes);
+            }
+            value["version"] = Value::from(CURRENT);
+            notes.push(format!("Migrated repo.json from format {} to {}", from, CURRENT));
+        },
+        Ordering::Equal => {},
+    }
+
+    Ok(Migrated { value, from, notes })
+}
+
+/// Convert a `CURRENT` document to an older format
+pub fn downgrade(value: &mut Value, target: &str) -> Result<(), SmoothlyError> {
+    if !KNOWN.contains(&target) {
+        return Err(SmoothlyError::GENERIC(format!("Unknown repo.json version {}, use one of {}", target, KNOWN.join(", "))));
+    }
+    if target == LEGACY {
+        if let Some(object) = value.as_object_mut() {
+            object.remove("version");
+            object.remove("imageChecksum");
+        }
+    } else {
+        value["version"] = Value::from(target);
+    }
+    Ok(())
+}
+
+/// Unversioned documents were often written by hand
+fn legacy(value: &mut Value, notes: &mut Vec<String>) {
+    let object = match value.as_object_mut() {
+        Some(o) => o,
+        None => return,
+    };
+    for (key, default) in &[("clientParameters", Value::from("")), ("requiredMods", Value::Array(Vec::new())), ("optionalMods", Value::Array(Vec::new())), ("servers", Value::Array(Vec::new()))] {
+        if !object.contains_key(*key) {
+            object.insert((*key).to_owned(), default.clone());
+            notes.push(format!("Added missing `{}`", key));
+        }
+    }
+    for list in &["requiredMods", "optionalMods"] {
+        if let Some(mods) = object.get_mut(*list).and_then(|m| m.as_array_mut()) {
+            for m in mods.iter_mut().filter_map(|m| m.as_object_mut()) {
+                rename(m, &["enabled", "enable"], "Enabled", notes);
+                if !m.contains_key("Enabled") {
+                    m.insert("Enabled".to_owned(), Value::from(true));
+                }
+            }
+        }
+    }
+    if let Some(servers) = object.get_mut("servers").and_then(|s| s.as_array_mut()) {
+        for s in servers.iter_mut().filter_map(|s| s.as_object_mut()) {
+            rename(s, &["battlEye", "battleye", "BattlEye"], "battleEye", notes);
+            if let Some(port) = s.get("port").and_then(|p| p.as_u64()) {
+                s.insert("port".to_owned(), Value::from(port.to_string()));
+            }
+            if !s.contains_key("password") {
+                s.insert("password".to_owned(), Value::from(""));
+            }
+        }
+    }
+}
+
+fn rename(object: &mut serde_json::Map<String, Value>, old: &[&str], new: &str, notes: &mut Vec<String>) {
+    for key in old {
+        if let Some(v) = object.remove(*key) {
+            if !object.contains_key(new) {
+                object.insert(new.to_owned(), v);
+                notes.push(format!("Renamed `{}` to `{}`", key, new));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn migrate_legacy() {
+        let migrated = migrate(json!({
+            "repoName": "Test",
+            "basePath": "C:\\mods",
+            "requiredMods": [{ "modName": "@cba", "enabled": false }, { "modName": "@ace" }],
+            "servers": [{ "name": "Main", "address": "127.0.0.1", "port": 2302, "battlEye": true }],
+        })).unwrap();
+        assert_eq!(migrated.from, LEGACY);
+        let value = migrated.value;
+        assert_eq!(value["version"], CURRENT);
+        assert_eq!(value["clientParameters"], "");
+        assert_eq!(value["optionalMods"], json!([]));
+        assert_eq!(value["requiredMods"], json!([{ "modName": "@cba", "Enabled": false }, { "modName": "@ace", "Enabled": true }]));
+        assert_eq!(value["servers"][0], json!({ "name": "Main", "address": "127.0.0.1", "port": "2302", "battleEye": true, "password": "" }));
+        assert!(migrated.notes.iter().any(|n| n == "Renamed `enabled` to `Enabled`"));
+        assert!(migrated.notes.iter().any(|n| n == "Renamed `battlEye` to `battleEye`"));
+    }
+
+    #[test]
+    fn migrate_current() {
+        let value = json!({ "repoName": "Test", "version": CURRENT });
+        let migrated = migrate(value.clone()).unwrap();
+        assert_eq!(migrated.value, value);
+        assert!(migrated.notes.is_empty());
+    }
+
+    #[test]
+    fn migrate_newer() {
+        let migrated = migrate(json!({ "version": "3.1.0.0" })).unwrap();
+        assert_eq!(migrated.value["version"], "3.1.0.0");
+        assert_eq!(migrated.notes.len(), 1);
+        assert!(migrate(json!({ "version": "4.0.0.0" })).is_err());
+        assert!(migrate(json!({ "version": "three" })).is_err());
+    }
+
+    #[test]
+    fn downgrade_legacy() {
+        let mut value = json!({ "repoName": "Test", "version": CURRENT, "imageChecksum": "ABCD" });
+        downgrade(&mut value, LEGACY).unwrap();
+        assert_eq!(value, json!({ "repoName": "Test" }));
+
+        let mut value = json!({ "version": CURRENT });
+        assert!(downgrade(&mut value, "1.0.0.0").is_err());
+        downgrade(&mut value, CURRENT).unwrap();
+        assert_eq!(value["version"], CURRENT);
+    }
+}
@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use fsnotify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::SmoothlyError;
+
+/// What needs pushing again after files changed
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    /// Only files inside these mod folders changed
+    Mods(Vec<String>),
+    /// The repository file, repo.png or something unknown changed
+    All,
+}
+
+/// Filesystem notifications for a basePath and the repository file
+pub struct Watch {
+    base: PathBuf,
+    repo: PathBuf,
+    window: Duration,
+    rx: Receiver<DebouncedEvent>,
+    _watcher: RecommendedWatcher,
+}
+
+impl Watch {
+    pub fn new(base: &Path, repo: &Path, window: Duration) -> Result<Self, SmoothlyError> {
+        let (tx, rx) = channel();
+        let error = |e: fsnotify::Error| SmoothlyError::GENERIC(format!("Unable to watch for changes: {}", e));
+        let base = base.canonicalize()?;
+        let repo = repo.canonicalize()?;
+        let mut watcher: RecommendedWatcher = Watcher::new(tx, window).map_err(error)?;
+        watcher.watch(&base, RecursiveMode::Recursive).map_err(error)?;
+        // Editors often replace the file, which would end a watch on the file itself
+        if !repo.starts_with(&base) {
+            if let Some(parent) = repo.parent() {
+                watcher.watch(parent, RecursiveMode::NonRecursive).map_err(error)?;
+            }
+        }
+        Ok(Self {
+            base,
+            repo,
+            window,
+            rx,
+            _watcher: watcher,
+        })
+    }
+
+    /// Block until files change and then stay untouched for the debounce window
+    pub fn wait(&self) -> Result<Change, SmoothlyError> {
+        let mut mods: Vec<String> = Vec::new();
+        let mut all = false;
+        let mut changed = false;
+        loop {
+            let event = if changed {
+                match self.rx.recv_timeout(self.window) {
+                    Ok(e) => e,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return Err(self.stopped()),
+                }
+            } else {
+                self.rx.recv().map_err(|_| self.stopped())?
+            };
+            let paths = match event {
+                DebouncedEvent::NoticeWrite(_) | DebouncedEvent::NoticeRemove(_) => continue,
+                DebouncedEvent::Create(p) | DebouncedEvent::Write(p) | DebouncedEvent::Chmod(p) | DebouncedEvent::Remove(p) => vec![p],
+                DebouncedEvent::Rename(from, to) => vec![from, to],
+                DebouncedEvent::Rescan => {
+                    all = true;
+                    vec![]
+                },
+                DebouncedEvent::Error(e, _) => return Err(SmoothlyError::GENERIC(format!("Unable to watch for changes: {}", e))),
+            };
+            for path in paths {
+                if path.starts_with(&self.base) {
+                    match self.folder(&path) {
+                        Some(name) => if !mods.contains(&name) { mods.push(name) },
+                        None => all = true,
+                    }
+                } else if path == self.repo {
+                    all = true;
+                } else {
+                    continue;
+                }
+                changed = true;
+            }
+            changed |= all;
+        }
+        Ok(if all { Change::All } else { Change::Mods(mods) })
+    }
+
+    /// The mod folder `path` is in, `None` for anything outside of one
+    fn folder(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.base).ok()?;
+        let mut components = relative.components();
+        let first = components.next()?;
+        components.next()?;
+        Some(first.as_os_str().to_str()?.to_owned())
+    }
+
+    fn stopped(&self) -> SmoothlyError {
+        SmoothlyError::GENERIC("Stopped watching for changes".to_owned())
+    }
+}